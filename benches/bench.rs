@@ -1,119 +1,400 @@
 use ahash::RandomState;
-use bench_hll::Container;
+use bench_hll::{Container, ContainerVisitor, splitmix64, visit_all};
 use criterion::BatchSize;
 use criterion::measurement::WallTime;
-use criterion::{BenchmarkGroup, Criterion, criterion_group, criterion_main};
-use hyperloglogplus::{HyperLogLog as HyperLogLogTrait, HyperLogLogPF, HyperLogLogPlus};
+use criterion::{
+    BenchmarkGroup, BenchmarkId, Criterion, Throughput, criterion_group, criterion_main,
+};
 use std::hint::black_box;
 
+/// Precision of the groups that aren't parameterized by precision.
 const P: u8 = 14;
 
-fn insert_bench<T: Container<u64>>(group: &mut BenchmarkGroup<'_, WallTime>, hll: &mut T) {
-    group.bench_function(T::name(), |b| {
-        b.iter(|| {
-            for x in 0..1000 {
-                black_box(hll.put(&x));
-            }
-        })
-    });
+/// Precisions of the `Insert*`, `Count` and `Fill` groups, the benchmark parameter.
+const PRECISIONS: std::ops::RangeInclusive<u8> = 4..=18;
+
+/// A sketch past the point where all HLL's have similar accuracy and are using the HLL
+/// algorithm (e.g. no sparse repr). Large precisions get at least 18 items per register.
+fn filled<T: Container<u64>>(precision: u8) -> T {
+    let mut hll = T::init(precision);
+    for x in 1000..=310_000.max(18 << precision) {
+        hll.put(&x);
+    }
+    hll
+}
+
+/// A sketch with `num` items, small enough that sketches with a sparse mode are still sparse.
+fn sparse_filled<T: Container<u64>>(num: u64) -> T {
+    let mut hll = T::init(P);
+    for x in 0..num {
+        hll.put(&x);
+    }
+    hll
+}
+
+/// Cardinalities of the sparse benches.
+const SPARSE_SIZES: [u64; 3] = [10, 100, 1000];
+
+/// First key that [`filled`] never inserts.
+const FRESH_KEYS: u64 = 1 << 40;
+
+/// Keys per iteration of the `Insert` and `InsertPrehashed` groups.
+const FRESH_BATCH: usize = 1000;
+
+/// A pre-generated buffer of fresh keys (or their hashes), walked [`FRESH_BATCH`] at a time so
+/// `Insert` and `InsertPrehashed` pay the same load per key. Each pass over the buffer XORs the
+/// values with a new salt, so they keep being fresh however many iterations criterion runs.
+struct Fresh {
+    buf: Vec<u64>,
+    pos: usize,
+    pass: u64,
+    salt: u64,
+}
+
+impl Fresh {
+    /// Small enough to stay in cache.
+    const LEN: usize = 64 * FRESH_BATCH;
+
+    fn keys() -> Self {
+        Self::new((FRESH_KEYS..FRESH_KEYS + Self::LEN as u64).collect())
+    }
+
+    fn hashes() -> Self {
+        Self::new(Self::keys().buf.into_iter().map(splitmix64).collect())
+    }
+
+    fn new(buf: Vec<u64>) -> Self {
+        Self {
+            buf,
+            pos: 0,
+            pass: 0,
+            salt: 0,
+        }
+    }
+
+    /// The next [`FRESH_BATCH`] values, to be XORed with the salt.
+    fn next_batch(&mut self) -> (&[u64], u64) {
+        if self.pos == self.buf.len() {
+            self.pos = 0;
+            self.pass += 1;
+            self.salt = splitmix64(self.pass);
+        }
+        let batch = &self.buf[self.pos..self.pos + FRESH_BATCH];
+        self.pos += FRESH_BATCH;
+        (batch, self.salt)
+    }
 }
 
-fn fill_bench<T: Container<u64>>(group: &mut BenchmarkGroup<'_, WallTime>, num: u64) {
-    let name = format!("items-{}-{}", num, T::name());
-    group.bench_function(name, |b| {
-        b.iter_batched(
-            || black_box(T::init(P)),
-            |mut hll| {
-                for x in 0..num {
-                    black_box(hll.put(&x));
-                }
-                let _ = black_box(hll.get_count());
-            },
-            BatchSize::SmallInput,
-        )
-    });
+/// Inserts 1000 never-seen keys per iteration into a filled sketch, from [`Fresh::keys`].
+/// Exact baselines are skipped, since they'd grow with every iteration.
+struct InsertBench<'a, 'b>(&'a mut BenchmarkGroup<'b, WallTime>, u8);
+
+impl ContainerVisitor for InsertBench<'_, '_> {
+    fn visit<T: Container<u64>>(&mut self) {
+        let precision = self.1;
+        if !T::supports_precision(precision) || T::capabilities().exact {
+            return;
+        }
+        let mut hll = filled::<T>(precision);
+        let mut keys = Fresh::keys();
+        self.0
+            .bench_function(BenchmarkId::new(T::name(), precision), |b| {
+                b.iter(|| {
+                    let (batch, salt) = keys.next_batch();
+                    for &x in batch {
+                        hll.put(black_box(&(x ^ salt)));
+                    }
+                })
+            });
+    }
 }
 
-fn count_bench<T: Container<u64>>(group: &mut BenchmarkGroup<'_, WallTime>, hll: &mut T) {
-    group.bench_function(T::name(), |b| b.iter(|| black_box(hll.get_count())));
+/// Re-inserts 1000 keys that are already in the filled sketch, so no insert changes a
+/// register: the best case no-op path.
+struct InsertUnchangedBench<'a, 'b>(&'a mut BenchmarkGroup<'b, WallTime>, u8);
+
+impl ContainerVisitor for InsertUnchangedBench<'_, '_> {
+    fn visit<T: Container<u64>>(&mut self) {
+        let precision = self.1;
+        if !T::supports_precision(precision) {
+            return;
+        }
+        let mut hll = filled::<T>(precision);
+        self.0
+            .bench_function(BenchmarkId::new(T::name(), precision), |b| {
+                b.iter(|| {
+                    for x in 1000..2000 {
+                        hll.put(black_box(&x));
+                    }
+                })
+            });
+    }
+}
+
+/// Precisions of the `InsertChanged` group: with at least 16 times more registers than keys,
+/// almost every key lands in an empty register and changes it.
+const INSERT_CHANGED_PRECISIONS: std::ops::RangeInclusive<u8> = 14..=18;
+
+/// Inserts 1000 keys into a new sketch, so almost every insert changes a register, unlike in a
+/// filled sketch where few fresh keys do. Sketches with a sparse mode are skipped, since a new
+/// one takes the sparse path instead. Creating the sketch isn't timed.
+struct InsertChangedBench<'a, 'b>(&'a mut BenchmarkGroup<'b, WallTime>, u8);
+
+impl ContainerVisitor for InsertChangedBench<'_, '_> {
+    fn visit<T: Container<u64>>(&mut self) {
+        let precision = self.1;
+        if !T::supports_precision(precision) || T::capabilities().sparse {
+            return;
+        }
+        self.0
+            .bench_function(BenchmarkId::new(T::name(), precision), |b| {
+                b.iter_batched(
+                    || T::init(precision),
+                    |mut hll| {
+                        for x in 0..1000 {
+                            hll.put(black_box(&x));
+                        }
+                        hll
+                    },
+                    BatchSize::SmallInput,
+                )
+            });
+    }
+}
+
+/// Inserts 1000 fresh, already computed hashes per iteration into a filled sketch, through
+/// [`Container::put_hash`], so only the register update is timed. The hashes are those of the
+/// keys `Insert` inserts, walked the same way, so the difference is the hasher.
+struct InsertPrehashedBench<'a, 'b>(&'a mut BenchmarkGroup<'b, WallTime>, u8);
+
+impl ContainerVisitor for InsertPrehashedBench<'_, '_> {
+    fn visit<T: Container<u64>>(&mut self) {
+        let precision = self.1;
+        if !T::supports_precision(precision) || !T::capabilities().prehashed {
+            return;
+        }
+        let mut hll = filled::<T>(precision);
+        let mut hashes = Fresh::hashes();
+        self.0
+            .bench_function(BenchmarkId::new(T::name(), precision), |b| {
+                b.iter(|| {
+                    let (batch, salt) = hashes.next_batch();
+                    for &hash in batch {
+                        hll.put_hash(black_box(hash ^ salt));
+                    }
+                })
+            });
+    }
+}
+
+/// Batch sizes of the `BatchInsert` group.
+const BATCH_SIZES: [usize; 6] = [8, 64, 512, 4096, 16_384, 65_536];
+
+/// Inserts a batch of fresh keys into a filled sketch with one [`Container::put_many`] call.
+/// Batches are generated in the untimed setup, and the throughput is reported per key, so
+/// batch sizes are comparable. Exact baselines are skipped, since they'd grow with every
+/// batch.
+struct BatchInsertBench<'a, 'b>(&'a mut BenchmarkGroup<'b, WallTime>, usize);
+
+impl ContainerVisitor for BatchInsertBench<'_, '_> {
+    fn visit<T: Container<u64>>(&mut self) {
+        if !T::supports_precision(P) || T::capabilities().exact {
+            return;
+        }
+        let size = self.1;
+        let mut hll = filled::<T>(P);
+        let mut next = FRESH_KEYS;
+        self.0.throughput(Throughput::Elements(size as u64));
+        self.0
+            .bench_function(BenchmarkId::new(T::name(), size), |b| {
+                b.iter_batched(
+                    || {
+                        let keys: Vec<u64> = (next..next + size as u64).collect();
+                        next += size as u64;
+                        keys
+                    },
+                    |keys| {
+                        hll.put_many(black_box(&keys));
+                        keys
+                    },
+                    BatchSize::LargeInput,
+                )
+            });
+    }
+}
+
+/// Inserts one new item into a sketch with `num` items. Each sample gets a new sketch, so the
+/// cardinality stays at `num`.
+struct InsertSparseBench<'a, 'b>(&'a mut BenchmarkGroup<'b, WallTime>, u64);
+
+impl ContainerVisitor for InsertSparseBench<'_, '_> {
+    fn visit<T: Container<u64>>(&mut self) {
+        if !T::supports_precision(P) {
+            return;
+        }
+        let num = self.1;
+        let name = format!("items-{}-{}", num, T::name());
+        self.0.bench_function(name, |b| {
+            b.iter_batched(
+                || sparse_filled::<T>(num),
+                |mut hll| {
+                    hll.put(black_box(&num));
+                    hll
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+}
+
+struct CountSparseBench<'a, 'b>(&'a mut BenchmarkGroup<'b, WallTime>, u64);
+
+impl ContainerVisitor for CountSparseBench<'_, '_> {
+    fn visit<T: Container<u64>>(&mut self) {
+        if !T::supports_precision(P) {
+            return;
+        }
+        let num = self.1;
+        let mut hll = sparse_filled::<T>(num);
+        let name = format!("items-{}-{}", num, T::name());
+        self.0
+            .bench_function(name, |b| b.iter(|| black_box(hll.get_count())));
+    }
+}
+
+struct FillBench<'a, 'b>(&'a mut BenchmarkGroup<'b, WallTime>, u64, u8);
+
+impl ContainerVisitor for FillBench<'_, '_> {
+    fn visit<T: Container<u64>>(&mut self) {
+        let (num, precision) = (self.1, self.2);
+        if !T::supports_precision(precision) {
+            return;
+        }
+        let name = format!("items-{}-{}", num, T::name());
+        self.0
+            .bench_function(BenchmarkId::new(name, precision), |b| {
+                b.iter_batched(
+                    || black_box(T::init(precision)),
+                    |mut hll| {
+                        for x in 0..num {
+                            hll.put(black_box(&x));
+                        }
+                        let _ = black_box(hll.get_count());
+                    },
+                    BatchSize::SmallInput,
+                )
+            });
+    }
+}
+
+struct CountBench<'a, 'b>(&'a mut BenchmarkGroup<'b, WallTime>, u8);
+
+impl ContainerVisitor for CountBench<'_, '_> {
+    fn visit<T: Container<u64>>(&mut self) {
+        let precision = self.1;
+        if !T::supports_precision(precision) {
+            return;
+        }
+        let mut hll = filled::<T>(precision);
+        self.0
+            .bench_function(BenchmarkId::new(T::name(), precision), |b| {
+                b.iter(|| black_box(hll.get_count()))
+            });
+    }
+}
+
+/// Merges a sketch of different items into one of the same size. HLL merges are idempotent,
+/// so every iteration does the same work.
+struct MergeBench<'a, 'b>(&'a mut BenchmarkGroup<'b, WallTime>);
+
+impl ContainerVisitor for MergeBench<'_, '_> {
+    fn visit<T: Container<u64>>(&mut self) {
+        if !T::supports_precision(P) || !T::capabilities().merge {
+            return;
+        }
+        let mut hll = filled::<T>(P);
+        let mut other = T::init(P);
+        for x in 310_000..620_000 {
+            other.put(&x);
+        }
+        self.0
+            .bench_function(T::name(), |b| b.iter(|| hll.merge(black_box(&other))));
+    }
 }
 
 fn bench(c: &mut Criterion) {
-    let mut lockless = hyperloglockless::HyperLogLog::<ahash::RandomState>::init(P);
-    let mut lockless_atomic = hyperloglockless::AtomicHyperLogLog::init(P);
-    let mut lockless_plus = hyperloglockless::HyperLogLogPlus::init(P);
-
-    let mut hll: HyperLogLogPF<u64, _> = HyperLogLogPF::init(P);
-    let mut hll_plus: HyperLogLogPlus<u64, _> = HyperLogLogPlus::init(P);
-
-    let mut prob = probabilistic_collections::hyperloglog::HyperLogLog::<u64, RandomState>::init(P);
-    let mut card = cardinality_estimator::CardinalityEstimator::<
-        u64,
-        ahash::AHasher,
-        { P as usize },
-        6,
-    >::init(P);
-    let mut amad = amadeus_streaming::HyperLogLog::<u64>::init(P);
-    let mut apache = bench_hll::apache_hll::HyperLogLog::<u64>::init(P);
-
-    // 310_000 is where all HLL's have similar accuracy and are using HLL algorithm (e.g. no sparse repr).
-    for x in 1000..=310_000 {
-        lockless.insert(&x);
-        lockless_atomic.insert(&x);
-        lockless_plus.insert(&x);
-        hll.insert(&x);
-        hll_plus.insert(&x);
-        prob.insert(&x);
-        card.insert(&x);
-        amad.push(&x);
-        apache.put(&x);
-    }
-    assert!(!lockless_plus.is_sparse());
+    for p in PRECISIONS {
+        assert!(!filled::<hyperloglockless::HyperLogLogPlus<RandomState>>(p).is_sparse());
+        assert!(!filled::<bench_hll::hllpp::HyperLogLogPlus<RandomState>>(p).is_sparse());
+    }
+    assert!(sparse_filled::<bench_hll::hllpp::HyperLogLogPlus<RandomState>>(1000).is_sparse());
 
     let mut group = c.benchmark_group("Insert");
-    insert_bench(&mut group, &mut lockless);
-    insert_bench(&mut group, &mut lockless_atomic);
-    insert_bench(&mut group, &mut lockless_plus);
-    insert_bench(&mut group, &mut hll);
-    insert_bench(&mut group, &mut hll_plus);
-    insert_bench(&mut group, &mut prob);
-    insert_bench(&mut group, &mut card);
-    insert_bench(&mut group, &mut amad);
-    insert_bench(&mut group, &mut apache);
+    for p in PRECISIONS {
+        visit_all(&mut InsertBench(&mut group, p));
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("InsertUnchanged");
+    for p in PRECISIONS {
+        visit_all(&mut InsertUnchangedBench(&mut group, p));
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("InsertChanged");
+    for p in INSERT_CHANGED_PRECISIONS {
+        visit_all(&mut InsertChangedBench(&mut group, p));
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("InsertPrehashed");
+    for p in PRECISIONS {
+        visit_all(&mut InsertPrehashedBench(&mut group, p));
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("BatchInsert");
+    for size in BATCH_SIZES {
+        visit_all(&mut BatchInsertBench(&mut group, size));
+    }
     group.finish();
 
     let mut group = c.benchmark_group("Count");
-    count_bench(&mut group, &mut lockless);
-    count_bench(&mut group, &mut lockless_atomic);
-    count_bench(&mut group, &mut lockless_plus);
-    count_bench(&mut group, &mut hll);
-    count_bench(&mut group, &mut hll_plus);
-    count_bench(&mut group, &mut prob);
-    count_bench(&mut group, &mut card);
-    count_bench(&mut group, &mut amad);
-    count_bench(&mut group, &mut apache);
+    for p in PRECISIONS {
+        visit_all(&mut CountBench(&mut group, p));
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("InsertSparse");
+    for num in SPARSE_SIZES {
+        visit_all(&mut InsertSparseBench(&mut group, num));
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("CountSparse");
+    for num in SPARSE_SIZES {
+        visit_all(&mut CountSparseBench(&mut group, num));
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("Merge");
+    visit_all(&mut MergeBench(&mut group));
     group.finish();
 }
 
+/// Sizes of the `Fill` group at the precisions other than [`P`], which gets the full sweep.
+const FILL_SIZES: [u64; 2] = [1024, 65_536];
+
 fn bench_fill(c: &mut Criterion) {
     let mut group = c.benchmark_group("Fill");
     let it = PowerIterator::new(2.0f64.sqrt());
     for num in it.into_iter().skip(12).take(31) {
-        fill_bench::<hyperloglockless::HyperLogLog<ahash::RandomState>>(&mut group, num);
-        fill_bench::<hyperloglockless::HyperLogLogPlus<ahash::RandomState>>(&mut group, num);
-
-        fill_bench::<HyperLogLogPF<u64, _>>(&mut group, num);
-        fill_bench::<HyperLogLogPlus<u64, _>>(&mut group, num);
-
-        fill_bench::<
-            cardinality_estimator::CardinalityEstimator<u64, ahash::AHasher, { P as usize }, 6>,
-        >(&mut group, num);
-        fill_bench::<amadeus_streaming::HyperLogLog<u64>>(&mut group, num);
-
-        fill_bench::<probabilistic_collections::hyperloglog::HyperLogLog<u64, ahash::RandomState>>(
-            &mut group, num,
-        );
-        fill_bench::<bench_hll::apache_hll::HyperLogLog<u64>>(&mut group, num);
+        visit_all(&mut FillBench(&mut group, num, P));
+    }
+    for num in FILL_SIZES {
+        for p in PRECISIONS.filter(|&p| p != P) {
+            visit_all(&mut FillBench(&mut group, num, p));
+        }
     }
     group.finish();
 }
@@ -2,25 +2,22 @@ import json
 import os
 import matplotlib.pyplot as plt
 from matplotlib import colormaps
+from impls import implementations
 
 plt.rcParams['font.size'] = 20
 
 cm = [colormaps['Set2'](i / 8) for i in range(8)]
-filters = [
-    ('hyperloglockless::HyperLogLog', cm[0]),
-    ('hyperloglockless::AtomicHyperLogLog', cm[0]),
-    ('hyperloglockless::HyperLogLogPlus', cm[0]),
 
-    ('cardinality_estimator::CardinalityEstimator', 'black'),
+def highlight(name):
+    if name.startswith('hyperloglockless'):
+        return cm[0]
+    if name == 'ull::UltraLogLog':
+        return cm[6]
+    if name.startswith('exact'):
+        return 'gray'
+    return 'black'
 
-    ('hyperloglogplus::HyperLogLogPF', 'black'),
-    ('hyperloglogplus::HyperLogLogPlus', 'black'),
-
-    ('amadeus_streaming::HyperLogLog', 'black'),
-    ('probabilistic_collections::HyperLogLog', 'black'),
-    
-    ('apache_datafusion::HyperLogLog', 'black'),
-]
+filters = [(name, highlight(name)) for name, _ in implementations()]
 
 filters = [(x.replace('::', '\n::').replace('_', '\n_'), y) for (x,y) in filters]
 
@@ -67,7 +64,14 @@ def get_immediate_subdirectories(a_dir):
 def get_non_reports(d):
      return [x for x in get_immediate_subdirectories(d) if x != 'report']
 
-def read_data():
+# Insert, Count and Fill are parameterized by precision, the bar plots show this one.
+PRECISION = 14
+
+def read_estimate(path):
+    with open(path + '\\base\\estimates.json') as f:
+        return float(json.load(f)['mean']['point_estimate'])
+
+def read_data(precision=PRECISION):
     result = {}
 
     bench_names = get_non_reports(directory)
@@ -77,16 +81,31 @@ def read_data():
             name = file_to_name(entity)
             if name is None: continue
 
-            with open(directory + '\\' + bench_name + '\\' + entity + '\\' + '\\base\\estimates.json') as f:
-                dic = json.load(f)
-                val = float(dic['mean']['point_estimate'])
-                result[bench_name][name] = val
+            path = directory + '\\' + bench_name + '\\' + entity
+            if not os.path.isdir(path + '\\base'):
+                path += '\\' + str(precision)
+                if not os.path.isdir(path): continue
+            result[bench_name][name] = read_estimate(path)
     return result
 
+def read_by_precision(bench_name):
+    '''{name: [(precision, time)]} of a group parameterized by precision.'''
+    result = {}
+    bench_dir = directory + '\\' + bench_name
+    for entity in get_non_reports(bench_dir):
+        name = file_to_name(entity)
+        if name is None: continue
+        for p in get_non_reports(bench_dir + '\\' + entity):
+            if is_input(p):
+                result.setdefault(name, []).append((int(p), read_estimate(bench_dir + '\\' + entity + '\\' + p)))
+    return {name: sorted(values) for name, values in result.items()}
+
 def plot(title, data, mult=1000.0, log=False):
     fig,ax = plt.subplots(1,1, figsize=(10,10))
     b = []
-    for name, color in filters:
+    # Implementations without the capability or precision aren't benchmarked
+    present = [(name, color) for name, color in filters if name in data]
+    for name, color in present:
         b.append(
             ax.bar(
                 name,
@@ -99,8 +118,8 @@ def plot(title, data, mult=1000.0, log=False):
                 alpha=1,
                 )
             )
-    names = [x for x,_ in filters]
-    y = [data[x] for x,_ in filters]
+    names = [x for x,_ in present]
+    y = [data[x] for x,_ in present]
     [autolabel(r, ax) for r in b] # set val above each bar
     plt.ylabel('Speed (ns)')
     ax.tick_params(axis='x', labelsize=11)
@@ -110,7 +129,24 @@ def plot(title, data, mult=1000.0, log=False):
     # ax.legend(b, names, loc = 'upper left', framealpha = 0.1)
     plt.show()
 
+def plot_precision(title, data, mult=1000.0):
+    fig,ax = plt.subplots(1,1, figsize=(10,10))
+    for name, c in filters:
+        if name not in data: continue
+        p, t = zip(*data[name])
+        ax.plot(p, [x / mult for x in t], color=c, label=name.replace('\n', ''), linewidth=3, marker='o')
+    ax.set_yscale('log')
+    plt.xlabel('Precision')
+    plt.ylabel('Speed (ns)')
+    plt.title(title)
+    plt.grid()
+    plt.legend(loc='upper left', fontsize=11)
+    plt.show()
+
 data = read_data()
-plot('HyperLogLog Insert Time (Precision = 14)', data['insert'])
-plot('HyperLogLog Count Time (Precision = 14)', data['count'], mult=1.0)
-plot('HyperLogLog Insert 8K & Count Time (Precision = 14)', data['fill'], mult=1.0)
\ No newline at end of file
+plot('HyperLogLog Insert Time (Precision = %d)' % PRECISION, data['insert'])
+plot('HyperLogLog Count Time (Precision = %d)' % PRECISION, data['count'], mult=1.0)
+plot('HyperLogLog Insert 8K & Count Time (Precision = %d)' % PRECISION, data['fill'], mult=1.0)
+plot('HyperLogLog Merge Time (Precision = 14)', data['merge'], mult=1.0)
+plot_precision('HyperLogLog Insert Time by Precision (hyperloglog: 14 only)', read_by_precision('insert'))
+plot_precision('HyperLogLog Count Time by Precision (hyperloglog: 14 only)', read_by_precision('count'), mult=1.0)
\ No newline at end of file
@@ -3,8 +3,8 @@ import matplotlib.pyplot as plt
 import csv
 from matplotlib.ticker import ScalarFormatter
 from matplotlib.ticker import FuncFormatter
-import matplotlib.colors as mcolors
 from matplotlib import colormaps
+from impls import implementations
 plt.rcParams['font.size'] = 20
 
 viridis = colormaps['viridis']
@@ -20,26 +20,24 @@ filters = [('hyperloglockless (%d bytes)' % (2**i), magma((i - min_size) * color
 alpha = 1
 lw = 3.5
 
+# Plot the raw estimates (the last three columns) instead of the reported,
+# bias-corrected ones. Only implementations with the raw_count capability have them.
+raw = False
+avg_col, min_col, max_col = (5, 6, 7) if raw else (1, 2, 3)
 
-def swap_color(c, i, j):
-    sparse_color = mcolors.to_rgb(cm[0])
-    rgb = list(mcolors.to_rgb(c))
-    rgb[i], rgb[j] = rgb[j], rgb[i]
-    return tuple(rgb)
+# Precision the plotted files were written at: 4 for Acc/ (acc() in main.rs), 14 for Sim/.
+PRECISION = 4
 
-cm = [colormaps['Set2'](i / 8) for i in range(8)]
+
+# The reference implementations in this crate are drawn thinner.
+reference = ['hllpp::HyperLogLogPlus', 'ull::UltraLogLog']
 
 filters = [
-    ('hyperloglogplus::HyperLogLogPlus',  cm[1], alpha, lw),
-    ('hyperloglogplus::HyperLogLogPF',  cm[2], alpha, lw),
-    ('cardinality_estimator::CardinalityEstimator',  cm[3], alpha, lw),
-    ('probabilistic_collections::HyperLogLog',  cm[4], alpha, lw),
-    ('amadeus_streaming::HyperLogLog', cm[5], alpha, lw),
-    ('apache_datafusion::HyperLogLog', swap_color(cm[5], 1, 2), alpha, lw),
-    ('hyperloglockless::HyperLogLog', cm[0], 1, lw),
-    ('hyperloglockless::HyperLogLogPlus', swap_color(cm[0], 1, 2), 1, lw),
-    # ('hyperloglockless (Old)', cm[6], 1, lw),
+    (name, color, 0.6, lw / 2) if name in reference else (name, color, alpha, lw)
+    for name, color in implementations(exact=False, **({'raw_count': True} if raw else {}))
 ]
+# hyperloglockless last, so it's drawn on top
+filters.sort(key=lambda f: f[0].startswith('hyperloglockless'))
 
 fig, ax = plt.subplots()
 
@@ -52,19 +50,26 @@ def custom_format(yy, _):
 for i, (name, color, aa, lw) in enumerate(filters):
     file_name = ('Acc/%s.csv' % name).replace('::', '__')
     print(file_name)
-    with open(file_name, 'r') as csvfile:
+    try:
+        csvfile = open(file_name, 'r')
+    except FileNotFoundError:
+        print('missing', file_name)
+        continue
+    with csvfile:
         data = []
         rows = csv.reader(csvfile, delimiter = ',')
         for row in rows:
-            if row[1] == 'NaN':
+            if len(row) <= max_col or row[avg_col] == 'NaN':
                 continue
             num_items = int(row[0])
-            avg_y = float(row[1])*100.0
-            min_y = float(row[2])*100.0
-            max_y = float(row[3])*100.0
+            avg_y = float(row[avg_col])*100.0
+            min_y = float(row[min_col])*100.0
+            max_y = float(row[max_col])*100.0
 
             data.append((num_items, avg_y, min_y, max_y))
 
+        if not data:
+            continue
         x,avg_y,min_y,max_y = zip(*data)
         
         ax.plot(x, avg_y, color=color, label=name, linewidth=lw, alpha=aa)
@@ -75,7 +80,7 @@ for i, (name, color, aa, lw) in enumerate(filters):
 plt.xlabel('True Number of Distinct Elements') 
 plt.ylabel('Error %') 
 
-plt.title('HyperLogLog Error, Lower is Better (Precision = 14)')
+plt.title('HyperLogLog %s Error, Lower is Better (Precision = %d)' % ('Raw Estimate' if raw else 'Corrected Estimate', PRECISION))
 
 # Crate Comparison
 
@@ -90,8 +95,9 @@ plt.gca().yaxis.set_major_formatter(FuncFormatter(custom_format))
 handles, labels = ax.get_legend_handles_labels()
 
 # make hyperlogloglockless appear on top
-handles = [handles.pop(), handles.pop()] + handles
-labels = [labels.pop(), labels.pop()] + labels
+num_lockless = sum(1 for l in labels if l.startswith('hyperloglockless'))
+handles = handles[::-1][:num_lockless] + handles[:-num_lockless or None]
+labels = labels[::-1][:num_lockless] + labels[:-num_lockless or None]
 
 plt.grid()
 # https://stackoverflow.com/questions/67033128/matplotlib-order-of-legend-entries
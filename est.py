@@ -0,0 +1,44 @@
+import matplotlib.pyplot as plt
+import csv
+from matplotlib import colormaps
+plt.rcParams['font.size'] = 20
+
+# Plots the output of `cargo run --release -- est`: the error of each estimator in
+# src/estimators.rs, all computed from the same apache_hll registers.
+
+lw = 3.5
+
+cm = [colormaps['Set2'](i / 8) for i in range(8)]
+
+filters = [
+    ('classic', cm[1]),
+    ('loglog_beta', cm[2]),
+    ('hllpp', 'black'),
+    ('ertl_improved', cm[5]),
+    ('ertl_ml', cm[6]),
+]
+
+fig, ax = plt.subplots()
+
+for name, color in filters:
+    file_name = 'Est/%s.csv' % name
+    print(file_name)
+    with open(file_name, 'r') as csvfile:
+        data = []
+        for row in csv.reader(csvfile, delimiter = ','):
+            if row[1] == 'NaN':
+                continue
+            data.append((int(row[0]), float(row[1]) * 100.0))
+        if not data:
+            continue
+        x, y = zip(*data)
+        ax.plot(x, y, color=color, label=name, linewidth=lw)
+
+ax.set_xscale('log')
+ax.set_yscale('log')
+plt.xlabel('True Number of Distinct Elements')
+plt.ylabel('Error %')
+plt.title('Estimator Error on Identical Registers (Precision = 14), Lower is Better')
+plt.grid()
+plt.legend(loc='lower right')
+plt.show()
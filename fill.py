@@ -5,42 +5,20 @@ import matplotlib.pyplot as plt
 from collections import defaultdict
 
 
-import matplotlib.colors as mcolors
-from matplotlib import colormaps
+from impls import implementations
 plt.rcParams['font.size'] = 18
 
 
 
-def swap_color(c, i, j):
-    sparse_color = mcolors.to_rgb(cm[0])
-    rgb = list(mcolors.to_rgb(c))
-    rgb[i], rgb[j] = rgb[j], rgb[i]
-    return tuple(rgb)
-
-cm = [colormaps['Set2'](i / 8) for i in range(8)]
-
-
-filters = {
-    'hyperloglogplus::HyperLogLogPlus': cm[1],
-    'hyperloglogplus::HyperLogLogPF':  cm[2],
-    'cardinality_estimator::CardinalityEstimator': cm[3], 
-    'probabilistic_collections::HyperLogLog':  cm[4],
-    'amadeus_streaming::HyperLogLog': cm[5],
-    'apache_datafusion::HyperLogLog': swap_color(cm[5], 1, 2),
-    'hyperloglockless::HyperLogLog': cm[0],
-    'hyperloglockless::HyperLogLogPlus': swap_color(cm[0], 1, 2),
-    # ('hyperloglockless (Old)', cm[6], 1, lw),
-}
+filters = dict(implementations())
 
+# Criterion lowercases the benchmark ids, e.g. hyperloglockless__hyperloglog
 def fix_name(s):
-    crate, ds = s.split('__')
-    ds = ds \
-    .replace('hyperloglog', 'HyperLogLog')\
-    .replace('plus', 'Plus') \
-    .replace('cardinalityestimator', 'CardinalityEstimator')\
-    .replace('pf', 'PF')
-    return crate + "::" + ds
-    
+    for name in filters:
+        if name.replace('::', '__').lower() == s.lower():
+            return name
+    return s.replace('__', '::')
+
 
 # --- CONFIGURATION ---
 # Map your crate names to specific hex codes or standard colors
@@ -53,17 +31,18 @@ COLORS = {
 
 DEFAULT_COLOR = "gray"
 BENCHMARK_GROUP = "Fill"
+PRECISION = 14
 # ---------------------
 
 def plot_criterion_results():
     results = defaultdict(list)
     
     # Target Criterion JSON estimates
-    path_pattern = f"target/criterion/{BENCHMARK_GROUP}/*/base/estimates.json"
+    path_pattern = f"target/criterion/{BENCHMARK_GROUP}/*/{PRECISION}/base/estimates.json"
     
     for file_path in glob.glob(path_pattern):
-        # Folder structure: target/criterion/Fill/items-<crate>-<num>/...
-        folder_name = file_path.split(os.sep)[-3]
+        # Folder structure: target/criterion/Fill/items-<num>-<crate>/<precision>/...
+        folder_name = file_path.split(os.sep)[-4]
         parts = folder_name.split('-')
         
         if len(parts) < 3:
@@ -94,7 +73,7 @@ def plot_criterion_results():
         line_color = COLORS.get(crate_name, DEFAULT_COLOR)
         x, y = zip(*[(xi, yi) for (xi, yi) in zip(x, y) if 64 < xi < 2**20])
         label = fix_name(crate_name)
-        color = filters[label]
+        color = filters.get(label, DEFAULT_COLOR)
         plt.plot(
             x, y, 
             marker='o', 
@@ -111,7 +90,7 @@ def plot_criterion_results():
 
     plt.xlabel('Number of Items', fontsize=18)
     plt.ylabel('Mean Execution Time (μs)', fontsize=18)
-    plt.title(f'HyperLogLog Performance (Lower is Better): Insert then Count', fontsize=18, fontweight='bold')
+    plt.title(f'HyperLogLog Performance (Lower is Better): Insert then Count (Precision = {PRECISION})', fontsize=18, fontweight='bold')
     
     plt.legend(loc='lower right')
     plt.grid(True, which="both", ls="--", alpha=0.3)
@@ -0,0 +1,43 @@
+import matplotlib.pyplot as plt
+import csv
+from impls import implementations
+plt.rcParams['font.size'] = 20
+
+# Plots GroupBy/*.csv written by `cargo run --release -- groupby`, with the columns
+# num_groups,present_groups,num_rows,bytes,bytes_per_group,ns_per_row,avg,min,max,rmse
+
+lw = 3.5
+
+fig, (ax_mem, ax_time, ax_err) = plt.subplots(1, 3)
+
+for name, color in implementations():
+    file_name = ('GroupBy/%s.csv' % name).replace('::', '__')
+    try:
+        csvfile = open(file_name, 'r')
+    except FileNotFoundError:
+        print('missing', file_name)
+        continue
+    with csvfile:
+        data = [(int(row[0]), int(row[3]), float(row[5]), float(row[9]))
+                for row in csv.reader(csvfile, delimiter = ',')]
+        if not data:
+            continue
+        x, num_bytes, ns, rmse = zip(*data)
+        ax_mem.plot(x, num_bytes, color=color, label=name, linewidth=lw)
+        ax_time.plot(x, ns, color=color, label=name, linewidth=lw)
+        ax_err.plot(x, [e * 100.0 for e in rmse], color=color, label=name, linewidth=lw)
+
+for ax in (ax_mem, ax_time, ax_err):
+    ax.set_xscale('log')
+    ax.set_yscale('log')
+    ax.set_xlabel('Number of Groups')
+    ax.grid()
+
+ax_mem.set_ylabel('Bytes')
+ax_mem.set_title('Total Memory')
+ax_time.set_ylabel('ns')
+ax_time.set_title('Time per Row')
+ax_err.set_ylabel('Error %')
+ax_err.set_title('RMSE per Group')
+ax_mem.legend(loc='upper left')
+plt.show()
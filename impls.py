@@ -0,0 +1,43 @@
+import csv
+import matplotlib.colors as mcolors
+from matplotlib import colormaps
+
+# The implementations in the comparison and their capabilities, read from
+# capabilities.csv. The accuracy sweep of main.rs writes it, `cargo run --release -- caps`
+# writes only that file.
+
+def swap_color(c, i, j):
+    rgb = list(mcolors.to_rgb(c))
+    rgb[i], rgb[j] = rgb[j], rgb[i]
+    return tuple(rgb)
+
+cm = [colormaps['Set2'](i / 8) for i in range(8)]
+
+# Colors for the known implementations, new ones are gray until added here.
+COLORS = {
+    'hyperloglockless::HyperLogLog': cm[0],
+    'hyperloglockless::AtomicHyperLogLog': cm[0],
+    'hyperloglockless::HyperLogLogPlus': swap_color(cm[0], 1, 2),
+    'hyperloglogplus::HyperLogLogPlus': cm[1],
+    'hyperloglogplus::HyperLogLogPF': cm[2],
+    'cardinality_estimator::CardinalityEstimator': cm[3],
+    'probabilistic_collections::HyperLogLog': cm[4],
+    'amadeus_streaming::HyperLogLog': cm[5],
+    'hyperloglog::HyperLogLog': cm[7],
+    'apache_datafusion::HyperLogLog': swap_color(cm[5], 1, 2),
+    'apache_datafusion::IncrementalHyperLogLog': swap_color(cm[5], 0, 2),
+    'hllpp::HyperLogLogPlus': 'black',
+    'ull::UltraLogLog': cm[6],
+    'exact::HashSet': 'black',
+    'exact::SortedVec': 'gray',
+}
+
+def implementations(**required):
+    '''(name, color) of each implementation whose capabilities match `required`,
+    e.g. implementations(exact=False), in the order of src/container.rs.'''
+    res = []
+    with open('capabilities.csv', 'r') as csvfile:
+        for row in csv.DictReader(csvfile):
+            if all((row[k] == 'true') == v for k, v in required.items()):
+                res.append((row['name'], COLORS.get(row['name'], 'gray')))
+    return res
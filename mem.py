@@ -0,0 +1,50 @@
+import matplotlib.pyplot as plt
+import csv
+from impls import implementations
+plt.rcParams['font.size'] = 20
+
+# Reads the 5th column (bytes) written by main.rs into Acc/*.csv and plots
+# memory vs cardinality, plus the memory-variance product
+# MVP = (relative error)^2 * bits, where lower means more accuracy per byte.
+
+lw = 3.5
+
+filters = implementations()
+
+fig, (ax_mem, ax_mvp) = plt.subplots(1, 2)
+
+for name, color in filters:
+    file_name = ('Acc/%s.csv' % name).replace('::', '__')
+    try:
+        csvfile = open(file_name, 'r')
+    except FileNotFoundError:
+        print('missing', file_name)
+        continue
+    with csvfile:
+        data = []
+        for row in csv.reader(csvfile, delimiter = ','):
+            if row[1] == 'NaN' or len(row) < 5:
+                continue
+            num_items = int(row[0])
+            avg = float(row[1])
+            num_bytes = int(row[4])
+            data.append((num_items, num_bytes, avg * avg * num_bytes * 8))
+
+        if not data:
+            continue
+        x, num_bytes, mvp = zip(*data)
+        ax_mem.plot(x, num_bytes, color=color, label=name, linewidth=lw)
+        ax_mvp.plot(x, mvp, color=color, label=name, linewidth=lw)
+
+for ax in (ax_mem, ax_mvp):
+    ax.set_xscale('log')
+    ax.set_yscale('log')
+    ax.set_xlabel('True Number of Distinct Elements')
+    ax.grid()
+
+ax_mem.set_ylabel('Bytes')
+ax_mem.set_title('Memory Footprint')
+ax_mvp.set_ylabel('Error$^2$ x Bits')
+ax_mvp.set_title('Memory-Variance Product, Lower is Better')
+ax_mem.legend(loc='upper left')
+plt.show()
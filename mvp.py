@@ -0,0 +1,50 @@
+import matplotlib.pyplot as plt
+import csv
+from impls import implementations
+plt.rcParams['font.size'] = 20
+
+# Plots the output of `cargo run --release -- mem`: measured RMSE against the
+# actual bytes used, for each implementation's native size parameter sweep.
+# The dashed lines have constant memory-variance product (MVP = rmse^2 * bits),
+# e.g. ~6.5 for HLL with 6-bit registers and ~8.7 with 8-bit registers.
+
+lw = 3.5
+
+filters = implementations(exact=False)
+
+fig, ax = plt.subplots()
+
+all_bytes = []
+for name, color in filters:
+    file_name = ('Mem/%s.csv' % name).replace('::', '__')
+    try:
+        csvfile = open(file_name, 'r')
+    except FileNotFoundError:
+        print('missing', file_name)
+        continue
+    with csvfile:
+        data = []
+        for row in csv.reader(csvfile, delimiter = ','):
+            if row[2] == 'NaN':
+                continue
+            data.append((int(row[1]), float(row[2]) * 100.0))
+        data.sort()
+        if not data:
+            continue
+        x, y = zip(*data)
+        all_bytes.extend(x)
+        ax.plot(x, y, color=color, label=name, linewidth=lw, marker='o')
+
+for mvp in [6.5, 8.7]:
+    xs = [min(all_bytes), max(all_bytes)]
+    ys = [100.0 * (mvp / (b * 8)) ** 0.5 for b in xs]
+    ax.plot(xs, ys, color='gray', linestyle='--', linewidth=1.5, label='MVP = %.1f' % mvp)
+
+ax.set_xscale('log', base=2)
+ax.set_yscale('log')
+plt.xlabel('Bytes Used')
+plt.ylabel('RMSE %')
+plt.title('HyperLogLog Error vs Memory (hyperloglog up to Precision = 14), Lower is Better')
+plt.grid()
+plt.legend(loc='upper right')
+plt.show()
@@ -0,0 +1,42 @@
+import matplotlib.pyplot as plt
+import csv
+from impls import implementations
+plt.rcParams['font.size'] = 20
+
+# Plots Sparse/*.csv written by `cargo run --release -- sparse`, with the columns
+# precision,trial,num_items,latency_ns,median_latency_ns,bytes_before,bytes_after,detection
+
+fig, (ax_items, ax_latency, ax_bytes) = plt.subplots(1, 3)
+
+for name, color in implementations(sparse=True):
+    file_name = ('Sparse/%s.csv' % name).replace('::', '__')
+    try:
+        csvfile = open(file_name, 'r')
+    except FileNotFoundError:
+        print('missing', file_name)
+        continue
+    with csvfile:
+        data = [(int(row[0]), int(row[2]), int(row[3]), int(row[4]), int(row[5]), int(row[6]))
+                for row in csv.reader(csvfile, delimiter = ',')]
+        if not data:
+            continue
+        p, items, latency, median, before, after = zip(*data)
+        ax_items.scatter(p, items, color=color, label=name)
+        ax_latency.scatter(p, latency, color=color, label=name)
+        ax_latency.scatter(p, median, color=color, marker='x')
+        ax_bytes.scatter(p, before, color=color, marker='v')
+        ax_bytes.scatter(p, after, color=color, marker='^', label=name)
+
+for ax in (ax_items, ax_latency, ax_bytes):
+    ax.set_yscale('log')
+    ax.set_xlabel('Precision')
+    ax.grid()
+
+ax_items.set_ylabel('Distinct Items')
+ax_items.set_title('Cardinality at Conversion')
+ax_latency.set_ylabel('ns')
+ax_latency.set_title('Converting Insert (x: Median Insert)')
+ax_bytes.set_ylabel('Bytes')
+ax_bytes.set_title('Memory Before (v) and After (^)')
+ax_items.legend(loc='upper left')
+plt.show()
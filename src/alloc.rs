@@ -0,0 +1,67 @@
+//! Counting global allocator used to attribute heap usage to individual sketches.
+//!
+//! Install [`CountingAllocator`] as the `#[global_allocator]` of a binary, then wrap
+//! operations in [`measure`] to get the net number of heap bytes they left allocated.
+//! Counters are kept per thread, so parallel trials (e.g. with rayon) don't see each other's
+//! allocations. Without the allocator installed every measurement is zero.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+thread_local! {
+    static ALLOCATED: Cell<isize> = const { Cell::new(0) };
+}
+
+#[inline]
+fn record(delta: isize) {
+    // `try_with` because the allocator is also called during thread teardown.
+    let _ = ALLOCATED.try_with(|a| a.set(a.get() + delta));
+}
+
+/// Wraps the system allocator and tracks net allocated bytes for each thread.
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc(layout) };
+        if !ptr.is_null() {
+            record(layout.size() as isize);
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc_zeroed(layout) };
+        if !ptr.is_null() {
+            record(layout.size() as isize);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        record(-(layout.size() as isize));
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = unsafe { System.realloc(ptr, layout, new_size) };
+        if !new_ptr.is_null() {
+            record(new_size as isize - layout.size() as isize);
+        }
+        new_ptr
+    }
+}
+
+/// Net heap bytes allocated by the current thread so far.
+#[inline]
+pub fn allocated() -> isize {
+    ALLOCATED.try_with(|a| a.get()).unwrap_or(0)
+}
+
+/// Runs `f` and returns its result along with the net heap bytes it left allocated.
+#[inline]
+pub fn measure<R>(f: impl FnOnce() -> R) -> (R, isize) {
+    let before = allocated();
+    let res = f();
+    (res, allocated() - before)
+}
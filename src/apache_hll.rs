@@ -40,6 +40,8 @@ use ahash::RandomState;
 use std::hash::Hash;
 use std::marker::PhantomData;
 
+mod kernels;
+
 /// The greater is P, the smaller the error.
 const HLL_P: usize = 14_usize;
 /// The number of bits of the hash value used determining the number of leading zeros
@@ -47,6 +49,8 @@ const HLL_Q: usize = 64_usize - HLL_P;
 const NUM_REGISTERS: usize = 1_usize << HLL_P;
 /// Mask to obtain index into the registers
 const HLL_P_MASK: u64 = (NUM_REGISTERS as u64) - 1;
+/// Number of elements [`HyperLogLog::add_all`] hashes before updating their registers
+const CHUNK: usize = 16;
 
 #[derive(Clone, Debug)]
 pub struct HyperLogLog<T>
@@ -110,21 +114,21 @@ where
     /// Adds an element to the HyperLogLog.
     pub fn add(&mut self, obj: &T) {
         let hash = self.hash_value(obj);
-        let index = (hash & HLL_P_MASK) as usize;
-        let p = ((hash >> HLL_P) | (1_u64 << HLL_Q)).trailing_zeros() + 1;
-        self.registers[index] = self.registers[index].max(p as u8);
+        self.add_hash(hash);
+    }
+
+    /// Adds an already hashed element to the HyperLogLog.
+    #[inline]
+    pub fn add_hash(&mut self, hash: u64) {
+        let (index, rank) = index_rank(hash);
+        self.registers[index] = self.registers[index].max(rank);
     }
 
     /// Get the register histogram (each value in register index into
     /// the histogram; u32 is enough because we only have 2**14=16384 registers
     #[inline]
     fn get_histogram(&self) -> [u32; HLL_Q + 2] {
-        let mut histogram = [0; HLL_Q + 2];
-        // hopefully this can be unrolled
-        for r in self.registers {
-            histogram[r as usize] += 1;
-        }
-        histogram
+        kernels::histogram(&self.registers)
     }
 
     /// Merge the other [`HyperLogLog`] into this one
@@ -135,22 +139,115 @@ where
             self.registers.len(),
             other.registers.len()
         );
-        for i in 0..self.registers.len() {
-            self.registers[i] = self.registers[i].max(other.registers[i]);
-        }
+        kernels::merge_max(&mut self.registers, &other.registers);
     }
 
     /// Guess the number of unique elements seen by the HyperLogLog.
     pub fn count(&self) -> usize {
-        let histogram = self.get_histogram();
-        let m = NUM_REGISTERS as f64;
-        let mut z = m * hll_tau((m - histogram[HLL_Q + 1] as f64) / m);
-        for i in histogram[1..=HLL_Q].iter().rev() {
-            z += *i as f64;
-            z *= 0.5;
+        estimate(&self.get_histogram())
+    }
+}
+
+/// The register index of `hash` (its low `HLL_P` bits) and the rank to store there (one
+/// more than the trailing zeros of the rest, capped at `HLL_Q + 1`).
+#[inline]
+fn index_rank(hash: u64) -> (usize, u8) {
+    let index = (hash & HLL_P_MASK) as usize;
+    let rank = ((hash >> HLL_P) | (1_u64 << HLL_Q)).trailing_zeros() + 1;
+    (index, rank as u8)
+}
+
+/// The estimate from the register histogram, see [`HyperLogLog::count`].
+fn estimate(histogram: &[u32; HLL_Q + 2]) -> usize {
+    let m = NUM_REGISTERS as f64;
+    let mut z = m * hll_tau((m - histogram[HLL_Q + 1] as f64) / m);
+    for i in histogram[1..=HLL_Q].iter().rev() {
+        z += *i as f64;
+        z *= 0.5;
+    }
+    z += m * hll_sigma(histogram[0] as f64 / m);
+    (0.5 / 2_f64.ln() * m * m / z).round() as usize
+}
+
+/// A [`HyperLogLog`] that keeps the register histogram up to date in
+/// [`IncrementalHyperLogLog::add`] and [`IncrementalHyperLogLog::merge`], so
+/// [`IncrementalHyperLogLog::count`] costs O(q) instead of a pass over all 2**14
+/// registers. In exchange, every add that raises a register also updates two
+/// histogram buckets, and the histogram adds 208 bytes.
+#[derive(Clone, Debug)]
+pub struct IncrementalHyperLogLog<T>
+where
+    T: Hash + ?Sized,
+{
+    hll: HyperLogLog<T>,
+    histogram: [u32; HLL_Q + 2],
+}
+
+impl<T> Default for IncrementalHyperLogLog<T>
+where
+    T: Hash + ?Sized,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> IncrementalHyperLogLog<T>
+where
+    T: Hash + ?Sized,
+{
+    /// Creates a new, empty IncrementalHyperLogLog.
+    pub fn new() -> Self {
+        let hll = HyperLogLog::new();
+        let histogram = hll.get_histogram();
+        Self { hll, histogram }
+    }
+
+    /// Adds an element to the IncrementalHyperLogLog.
+    pub fn add(&mut self, obj: &T) {
+        let hash = self.hll.hash_value(obj);
+        self.add_hash(hash);
+    }
+
+    /// Adds an already hashed element to the IncrementalHyperLogLog.
+    #[inline]
+    pub fn add_hash(&mut self, hash: u64) {
+        let (index, rank) = index_rank(hash);
+        self.update_register(index, rank);
+    }
+
+    #[inline]
+    fn update_register(&mut self, index: usize, rank: u8) {
+        let old = self.hll.registers[index];
+        if rank > old {
+            self.hll.registers[index] = rank;
+            self.histogram[old as usize] -= 1;
+            self.histogram[rank as usize] += 1;
         }
-        z += m * hll_sigma(histogram[0] as f64 / m);
-        (0.5 / 2_f64.ln() * m * m / z).round() as usize
+    }
+
+    /// Merge the other [`IncrementalHyperLogLog`] into this one
+    ///
+    /// Merges the registers with the vectorized kernel and then rebuilds the histogram, which
+    /// is faster than updating it register by register.
+    pub fn merge(&mut self, other: &IncrementalHyperLogLog<T>) {
+        self.hll.merge(&other.hll);
+        self.histogram = self.hll.get_histogram();
+    }
+
+    /// Guess the number of unique elements seen by the IncrementalHyperLogLog,
+    /// the same as [`HyperLogLog::count`] on the same registers.
+    pub fn count(&self) -> usize {
+        estimate(&self.histogram)
+    }
+}
+
+impl<T> AsRef<[u8]> for IncrementalHyperLogLog<T>
+where
+    T: Hash + ?Sized,
+{
+    fn as_ref(&self) -> &[u8] {
+        &self.hll.registers
     }
 }
 
@@ -158,7 +255,7 @@ where
 /// "New cardinality estimation algorithms for HyperLogLog sketches"
 /// Otmar Ertl, arXiv:1702.01284
 #[inline]
-fn hll_sigma(x: f64) -> f64 {
+pub(crate) fn hll_sigma(x: f64) -> f64 {
     if x == 1. {
         f64::INFINITY
     } else {
@@ -182,7 +279,7 @@ fn hll_sigma(x: f64) -> f64 {
 /// "New cardinality estimation algorithms for HyperLogLog sketches"
 /// Otmar Ertl, arXiv:1702.01284
 #[inline]
-fn hll_tau(x: f64) -> f64 {
+pub(crate) fn hll_tau(x: f64) -> f64 {
     if x == 0.0 || x == 1.0 {
         0.0
     } else {
@@ -211,6 +308,46 @@ where
     }
 }
 
+impl<T> crate::sim::RegisterSketch for HyperLogLog<T>
+where
+    T: Hash + ?Sized,
+{
+    fn index_bits(&self) -> u32 {
+        HLL_P as u32
+    }
+
+    fn max_rank(&self) -> u8 {
+        (HLL_Q + 1) as u8
+    }
+
+    fn update_register(&mut self, index: usize, rank: u8) {
+        self.registers[index] = self.registers[index].max(rank);
+    }
+}
+
+impl<T> HyperLogLog<T>
+where
+    T: Hash,
+{
+    /// Adds a batch of elements to the HyperLogLog.
+    ///
+    /// Hashes a chunk of elements first and then updates their registers, so the hashing
+    /// has no dependency on the register loads and stores and the loads of a chunk can
+    /// be in flight at once. Same result as calling [`HyperLogLog::add`] on each element.
+    pub fn add_all(&mut self, objs: &[T]) {
+        let mut chunks = objs.chunks_exact(CHUNK);
+        for chunk in chunks.by_ref() {
+            let hashes: [u64; CHUNK] = std::array::from_fn(|i| self.hash_value(&chunk[i]));
+            for hash in hashes {
+                self.add_hash(hash);
+            }
+        }
+        for obj in chunks.remainder() {
+            self.add(obj);
+        }
+    }
+}
+
 impl<T> Extend<T> for HyperLogLog<T>
 where
     T: Hash,
@@ -235,7 +372,7 @@ where
 
 #[cfg(test)]
 mod tests {
-    use super::{HyperLogLog, NUM_REGISTERS};
+    use super::{HyperLogLog, IncrementalHyperLogLog, NUM_REGISTERS, SEED};
 
     fn compare_with_delta(got: usize, expected: usize) {
         let expected = expected as f64;
@@ -368,4 +505,53 @@ mod tests {
         }
         compare_with_delta(hll.count(), 1000);
     }
+
+    #[test]
+    fn test_add_all() {
+        let items: Vec<u64> = (0..100_003).collect();
+        for len in [0, 1, 15, 16, 17, 1000, items.len()] {
+            let mut hll = HyperLogLog::<u64>::new();
+            let mut batched = HyperLogLog::<u64>::new();
+            for i in &items[..len] {
+                hll.add(i);
+            }
+            batched.add_all(&items[..len]);
+            assert_eq!(hll.registers, batched.registers);
+        }
+    }
+
+    #[test]
+    fn test_incremental() {
+        let mut hll = HyperLogLog::<u64>::new();
+        let mut incremental = IncrementalHyperLogLog::<u64>::new();
+        assert_eq!(incremental.count(), 0);
+        for i in 0..100_000u64 {
+            hll.add(&i);
+            incremental.add(&i);
+            if i % 1000 == 0 {
+                assert_eq!(hll.count(), incremental.count());
+            }
+        }
+        assert_eq!(incremental.histogram, hll.get_histogram());
+
+        let mut other = IncrementalHyperLogLog::<u64>::new();
+        for i in 50_000..200_000u64 {
+            hll.add(&i);
+            other.add(&i);
+        }
+        incremental.merge(&other);
+        assert_eq!(incremental.histogram, hll.get_histogram());
+        assert_eq!(hll.count(), incremental.count());
+    }
+
+    #[test]
+    fn test_add_hash() {
+        let mut hll = HyperLogLog::<u64>::new();
+        let mut prehashed = HyperLogLog::<u64>::new();
+        for i in 0..100_000u64 {
+            hll.add(&i);
+            prehashed.add_hash(SEED.hash_one(i));
+        }
+        assert_eq!(hll.registers, prehashed.registers);
+    }
 }
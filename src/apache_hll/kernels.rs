@@ -0,0 +1,281 @@
+//! Register kernels of [`super::HyperLogLog`]: the register-wise max of a merge and the
+//! register histogram of a count.
+//!
+//! On x86_64 the AVX2 or SSE2 version is picked at runtime, elsewhere the portable scalar
+//! version runs. The scalar versions are also the reference the others are tested against.
+
+/// Sets every register of `dst` to the max of itself and the same register of `src`.
+pub(super) fn merge_max(dst: &mut [u8], src: &[u8]) {
+    assert_eq!(dst.len(), src.len());
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx2") {
+            // SAFETY: AVX2 is available.
+            return unsafe { x86::merge_max_avx2(dst, src) };
+        }
+        if is_x86_feature_detected!("sse2") {
+            // SAFETY: SSE2 is available.
+            return unsafe { x86::merge_max_sse2(dst, src) };
+        }
+    }
+    merge_max_scalar(dst, src)
+}
+
+/// Number of registers with each value. Panics if a register is `N` or larger.
+pub(super) fn histogram<const N: usize>(registers: &[u8]) -> [u32; N] {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx2") {
+            // SAFETY: AVX2 is available.
+            return unsafe { x86::histogram_avx2(registers) };
+        }
+        if is_x86_feature_detected!("sse2") {
+            // SAFETY: SSE2 is available.
+            return unsafe { x86::histogram_sse2(registers) };
+        }
+    }
+    histogram_scalar(registers)
+}
+
+fn merge_max_scalar(dst: &mut [u8], src: &[u8]) {
+    for (d, s) in dst.iter_mut().zip(src) {
+        *d = (*d).max(*s);
+    }
+}
+
+fn histogram_scalar<const N: usize>(registers: &[u8]) -> [u32; N] {
+    let mut histogram = [0; N];
+    for &r in registers {
+        histogram[r as usize] += 1;
+    }
+    histogram
+}
+
+/// The vectorized histogram counts the registers equal to each value between the smallest
+/// and largest register, one compare per vector and value. Registers of a filled p=14 sketch
+/// span about 20 values, so that is fewer instructions than one dependent increment per
+/// register, but a single high register adds a pass per value, so wider spans fall back to
+/// the scalar histogram. Matches are summed in `u8` lanes, flushed to `u64` before they can
+/// overflow.
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    /// Vectors summed into the `u8` lanes before flushing them.
+    const FLUSH: usize = 255;
+    /// Vectors per iteration of the merge loops.
+    const UNROLL: usize = 4;
+    /// Widest span of register values (`max - min + 1`) that the histograms count with compare
+    /// passes. Measured on a filled p=14 sketch with one register raised, the scalar histogram
+    /// takes 13-17µs at any span, AVX2 12µs at span 40 and 13-15µs at 48 to 51, and SSE2 10-11µs
+    /// at span 24 and 14-15µs at 28.
+    const AVX2_MAX_SPAN: u8 = 40;
+    const SSE2_MAX_SPAN: u8 = 24;
+
+    #[target_feature(enable = "avx2")]
+    pub(super) fn merge_max_avx2(dst: &mut [u8], src: &[u8]) {
+        let mut dst_chunks = dst.chunks_exact_mut(32 * UNROLL);
+        let mut src_chunks = src.chunks_exact(32 * UNROLL);
+        for (d, s) in dst_chunks.by_ref().zip(src_chunks.by_ref()) {
+            let (d, s) = (d.as_mut_ptr() as *mut __m256i, s.as_ptr() as *const __m256i);
+            for k in 0..UNROLL {
+                // SAFETY: both chunks are `UNROLL` vectors, and the loads and store are
+                // unaligned.
+                unsafe {
+                    let a = _mm256_loadu_si256(d.add(k));
+                    let b = _mm256_loadu_si256(s.add(k));
+                    _mm256_storeu_si256(d.add(k), _mm256_max_epu8(a, b));
+                }
+            }
+        }
+        super::merge_max_scalar(dst_chunks.into_remainder(), src_chunks.remainder());
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub(super) fn merge_max_sse2(dst: &mut [u8], src: &[u8]) {
+        let mut dst_chunks = dst.chunks_exact_mut(16 * UNROLL);
+        let mut src_chunks = src.chunks_exact(16 * UNROLL);
+        for (d, s) in dst_chunks.by_ref().zip(src_chunks.by_ref()) {
+            let (d, s) = (d.as_mut_ptr() as *mut __m128i, s.as_ptr() as *const __m128i);
+            for k in 0..UNROLL {
+                // SAFETY: both chunks are `UNROLL` vectors, and the loads and store are
+                // unaligned.
+                unsafe {
+                    let a = _mm_loadu_si128(d.add(k));
+                    let b = _mm_loadu_si128(s.add(k));
+                    _mm_storeu_si128(d.add(k), _mm_max_epu8(a, b));
+                }
+            }
+        }
+        super::merge_max_scalar(dst_chunks.into_remainder(), src_chunks.remainder());
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) fn histogram_avx2<const N: usize>(registers: &[u8]) -> [u32; N] {
+        let chunks = registers.chunks_exact(32);
+        let mut histogram: [u32; N] = super::histogram_scalar(chunks.remainder());
+        let body = &registers[..registers.len() - chunks.remainder().len()];
+        if body.is_empty() {
+            return histogram;
+        }
+        let (mut min, mut max) = (_mm256_set1_epi8(-1), _mm256_setzero_si256());
+        for chunk in body.chunks_exact(32) {
+            // SAFETY: the chunk is 32 bytes, and the load is unaligned.
+            let v = unsafe { _mm256_loadu_si256(chunk.as_ptr() as *const __m256i) };
+            min = _mm256_min_epu8(min, v);
+            max = _mm256_max_epu8(max, v);
+        }
+        let (mut mins, mut maxs) = ([0u8; 32], [0u8; 32]);
+        // SAFETY: the arrays are 32 bytes, and the stores are unaligned.
+        unsafe {
+            _mm256_storeu_si256(mins.as_mut_ptr() as *mut __m256i, min);
+            _mm256_storeu_si256(maxs.as_mut_ptr() as *mut __m256i, max);
+        }
+        let (min, max) = (
+            mins.into_iter().min().unwrap(),
+            maxs.into_iter().max().unwrap(),
+        );
+        if max - min >= AVX2_MAX_SPAN {
+            return super::histogram_scalar(registers);
+        }
+        let zero = _mm256_setzero_si256();
+        for value in min..=max {
+            let needle = _mm256_set1_epi8(value as i8);
+            let mut total = zero;
+            for block in body.chunks(32 * FLUSH) {
+                let mut matches = zero;
+                for chunk in block.chunks_exact(32) {
+                    // SAFETY: the chunk is 32 bytes, and the load is unaligned.
+                    let v = unsafe { _mm256_loadu_si256(chunk.as_ptr() as *const __m256i) };
+                    // Equal lanes are all ones, i.e. -1.
+                    matches = _mm256_sub_epi8(matches, _mm256_cmpeq_epi8(v, needle));
+                }
+                total = _mm256_add_epi64(total, _mm256_sad_epu8(matches, zero));
+            }
+            let mut lanes = [0u64; 4];
+            // SAFETY: `lanes` is 32 bytes, and the store is unaligned.
+            unsafe { _mm256_storeu_si256(lanes.as_mut_ptr() as *mut __m256i, total) };
+            histogram[value as usize] += lanes.iter().sum::<u64>() as u32;
+        }
+        histogram
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub(super) fn histogram_sse2<const N: usize>(registers: &[u8]) -> [u32; N] {
+        let chunks = registers.chunks_exact(16);
+        let mut histogram: [u32; N] = super::histogram_scalar(chunks.remainder());
+        let body = &registers[..registers.len() - chunks.remainder().len()];
+        if body.is_empty() {
+            return histogram;
+        }
+        let (mut min, mut max) = (_mm_set1_epi8(-1), _mm_setzero_si128());
+        for chunk in body.chunks_exact(16) {
+            // SAFETY: the chunk is 16 bytes, and the load is unaligned.
+            let v = unsafe { _mm_loadu_si128(chunk.as_ptr() as *const __m128i) };
+            min = _mm_min_epu8(min, v);
+            max = _mm_max_epu8(max, v);
+        }
+        let (mut mins, mut maxs) = ([0u8; 16], [0u8; 16]);
+        // SAFETY: the arrays are 16 bytes, and the stores are unaligned.
+        unsafe {
+            _mm_storeu_si128(mins.as_mut_ptr() as *mut __m128i, min);
+            _mm_storeu_si128(maxs.as_mut_ptr() as *mut __m128i, max);
+        }
+        let (min, max) = (
+            mins.into_iter().min().unwrap(),
+            maxs.into_iter().max().unwrap(),
+        );
+        if max - min >= SSE2_MAX_SPAN {
+            return super::histogram_scalar(registers);
+        }
+        let zero = _mm_setzero_si128();
+        for value in min..=max {
+            let needle = _mm_set1_epi8(value as i8);
+            let mut total = zero;
+            for block in body.chunks(16 * FLUSH) {
+                let mut matches = zero;
+                for chunk in block.chunks_exact(16) {
+                    // SAFETY: the chunk is 16 bytes, and the load is unaligned.
+                    let v = unsafe { _mm_loadu_si128(chunk.as_ptr() as *const __m128i) };
+                    // Equal lanes are all ones, i.e. -1.
+                    matches = _mm_sub_epi8(matches, _mm_cmpeq_epi8(v, needle));
+                }
+                total = _mm_add_epi64(total, _mm_sad_epu8(matches, zero));
+            }
+            let mut lanes = [0u64; 2];
+            // SAFETY: `lanes` is 16 bytes, and the store is unaligned.
+            unsafe { _mm_storeu_si128(lanes.as_mut_ptr() as *mut __m128i, total) };
+            histogram[value as usize] += lanes.iter().sum::<u64>() as u32;
+        }
+        histogram
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    /// Register values like a sketch's: mostly small, a few up to the max rank.
+    fn registers(len: usize, seed: u64) -> Vec<u8> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..len)
+            .map(|_| match rng.gen_range(0..10) {
+                0 => rng.gen_range(0..=51),
+                _ => rng.gen_range(0..8),
+            })
+            .collect()
+    }
+
+    const LENS: [usize; 8] = [0, 1, 15, 16, 33, 255 * 32 + 5, 16_384, 16_384 + 17];
+
+    #[test]
+    fn test_merge_max() {
+        for (i, len) in LENS.into_iter().enumerate() {
+            let src = registers(len, 2 * i as u64);
+            let dst = registers(len, 2 * i as u64 + 1);
+            let mut expected = dst.clone();
+            merge_max_scalar(&mut expected, &src);
+
+            let mut got = dst.clone();
+            merge_max(&mut got, &src);
+            assert_eq!(got, expected, "{len}");
+            #[cfg(target_arch = "x86_64")]
+            {
+                if is_x86_feature_detected!("avx2") {
+                    let mut got = dst.clone();
+                    unsafe { x86::merge_max_avx2(&mut got, &src) };
+                    assert_eq!(got, expected, "avx2 {len}");
+                }
+                let mut got = dst.clone();
+                unsafe { x86::merge_max_sse2(&mut got, &src) };
+                assert_eq!(got, expected, "sse2 {len}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_histogram() {
+        for (i, len) in LENS.into_iter().enumerate() {
+            let mut regs = registers(len, i as u64);
+            // A long run of one value, so the matches of whole blocks fill the `u8` lanes.
+            regs.extend(std::iter::repeat_n(3, 255 * 32 * 2));
+            // Spans narrow enough for the compare passes, the full range falls back to scalar.
+            let narrow: Vec<u8> = regs.iter().map(|&r| r % 20).collect();
+            for regs in [&regs[..len], &regs[..], &narrow[..len], &narrow[..]] {
+                let expected = histogram_scalar::<52>(regs);
+                assert_eq!(histogram::<52>(regs), expected, "{len}");
+                #[cfg(target_arch = "x86_64")]
+                {
+                    if is_x86_feature_detected!("avx2") {
+                        let got = unsafe { x86::histogram_avx2::<52>(regs) };
+                        assert_eq!(got, expected, "avx2 {len}");
+                    }
+                    let got = unsafe { x86::histogram_sse2::<52>(regs) };
+                    assert_eq!(got, expected, "sse2 {len}");
+                }
+            }
+        }
+    }
+}
@@ -1,29 +1,237 @@
 use hyperloglogplus::HyperLogLog as _;
+use std::fmt;
 use std::hash::BuildHasher;
 use std::hash::Hash;
 
+/// The parameter an implementation natively uses to size itself.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SizeParam {
+    Precision(u8),
+    ErrorRate(f64),
+}
+
+impl fmt::Display for SizeParam {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SizeParam::Precision(p) => write!(f, "p={}", p),
+            SizeParam::ErrorRate(e) => write!(f, "err={}", e),
+        }
+    }
+}
+
+/// Seeds of [`FixedState`].
+const FIXED_SEEDS: ahash::RandomState = ahash::RandomState::with_seeds(
+    0x243f_6a88_85a3_08d3,
+    0x1319_8a2e_0370_7344,
+    0xa409_3822_299f_31d0,
+    0x082e_fa98_ec4e_6c89,
+);
+
+/// The hasher of every implementation in [`visit_all`]: ahash with fixed seeds, so all
+/// sketches of an implementation hash alike, merges agree with the union, and runs are
+/// repeatable.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FixedState;
+
+impl FixedState {
+    /// [`FixedState`] as an `ahash::RandomState`, for the crates that only take that.
+    pub fn ahash() -> ahash::RandomState {
+        FIXED_SEEDS.clone()
+    }
+}
+
+impl BuildHasher for FixedState {
+    type Hasher = ahash::AHasher;
+
+    fn build_hasher(&self) -> ahash::AHasher {
+        FIXED_SEEDS.build_hasher()
+    }
+}
+
+/// Error rates from ~p=4 to ~p=18 in half-precision steps, for crates sized by error rate.
+pub fn error_rate_sweep() -> Vec<SizeParam> {
+    (8..=36)
+        .map(|half_p| SizeParam::ErrorRate(1.04 / 2.0f64.powf(half_p as f64 / 4.0)))
+        .collect()
+}
+
+/// What an implementation supports beyond inserting and counting, as wired up through its
+/// [`Container`] impl, so the harness and benches only run the experiments that apply.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Capabilities {
+    /// [`Container::merge`] is implemented.
+    pub merge: bool,
+    /// Inserts take `&self`, so threads can share one sketch without a lock.
+    pub concurrent: bool,
+    /// Small cardinalities are kept in a sparse representation before switching to registers.
+    pub sparse: bool,
+    /// [`Container::get_raw_count`] returns the raw estimate, without bias correction.
+    pub raw_count: bool,
+    /// Accepts any `Hash` key, not just `u64`.
+    pub generic_keys: bool,
+    /// Counts exactly, a baseline rather than a sketch.
+    pub exact: bool,
+    /// [`Container::peek_count`] counts through `&self`, so concurrent readers can share a
+    /// read lock.
+    pub shared_count: bool,
+    /// [`Container::put_hash`] inserts an already computed 64-bit hash, skipping the hasher.
+    pub prehashed: bool,
+}
+
+impl Capabilities {
+    /// Names of the fields, in the order of [`Capabilities::flags`].
+    pub const NAMES: [&'static str; 8] = [
+        "merge",
+        "concurrent",
+        "sparse",
+        "raw_count",
+        "generic_keys",
+        "exact",
+        "shared_count",
+        "prehashed",
+    ];
+
+    pub fn flags(&self) -> [bool; 8] {
+        [
+            self.merge,
+            self.concurrent,
+            self.sparse,
+            self.raw_count,
+            self.generic_keys,
+            self.exact,
+            self.shared_count,
+            self.prehashed,
+        ]
+    }
+}
+
 pub trait Container<X: Hash> {
     fn put(&mut self, s: &X);
+    /// Inserts a batch of items. Implementations with a batch path override the default loop.
+    fn put_many(&mut self, items: &[X]) {
+        for x in items {
+            self.put(x);
+        }
+    }
+    /// The estimate the implementation reports, bias corrected where it offers a correction.
     fn get_count(&mut self) -> f64;
     fn init(precision: u8) -> Self;
     fn name() -> &'static str;
+
+    /// The raw estimate without bias correction, where the implementation offers both.
+    fn get_raw_count(&mut self) -> Option<f64> {
+        None
+    }
+
+    /// Same estimate as [`Container::get_count`] without mutating the sketch. `None` for
+    /// implementations that have to flush buffered state to count, and so need exclusive
+    /// access. Implementations return `Some` either always or never, as declared by
+    /// [`Capabilities::shared_count`].
+    fn peek_count(&self) -> Option<f64> {
+        None
+    }
+
+    /// Whether the sketch is still in its sparse representation. `None` for implementations
+    /// without a sparse mode or that don't expose it.
+    fn is_sparse(&self) -> Option<bool> {
+        None
+    }
+
+    fn capabilities() -> Capabilities {
+        Capabilities::default()
+    }
+
+    /// Inserts an item by its 64-bit hash, as if [`Container::put`] had hashed it to `hash`.
+    /// Only implemented if [`Capabilities::prehashed`] is set.
+    fn put_hash(&mut self, _hash: u64) {
+        panic!("{} does not support prehashed inserts", Self::name())
+    }
+
+    /// Merges `other` into `self`. Only implemented if [`Capabilities::merge`] is set.
+    fn merge(&mut self, _other: &Self)
+    where
+        Self: Sized,
+    {
+        panic!("{} does not support merge", Self::name())
+    }
+
+    /// Whether [`Container::init`] accepts `precision`. `init` panics on the others, so
+    /// precision sweeps check this first and mark the cell unsupported.
+    fn supports_precision(precision: u8) -> bool {
+        (4..=18).contains(&precision)
+    }
+
+    /// Values of the native size parameter swept by the memory-efficiency comparison.
+    fn size_params() -> Vec<SizeParam> {
+        (4..=18)
+            .filter(|&p| Self::supports_precision(p))
+            .map(SizeParam::Precision)
+            .collect()
+    }
+
+    /// Creates an empty sketch from its native size parameter.
+    fn init_with(param: SizeParam) -> Self
+    where
+        Self: Sized,
+    {
+        match param {
+            SizeParam::Precision(p) => Self::init(p),
+            SizeParam::ErrorRate(_) => panic!("{} is not sized by error rate", Self::name()),
+        }
+    }
 }
 
-impl<S: BuildHasher + Default> Container<u64> for hyperloglockless::HyperLogLog<S> {
+impl<S: BuildHasher + Clone + Default> Container<u64> for hyperloglockless::HyperLogLog<S> {
     #[inline]
     fn put(&mut self, s: &u64) {
         self.insert(s);
     }
     #[inline]
+    fn put_hash(&mut self, hash: u64) {
+        self.insert_hash(hash);
+    }
+    #[inline]
     fn get_count(&mut self) -> f64 {
         self.raw_count()
     }
+    fn peek_count(&self) -> Option<f64> {
+        Some(self.raw_count())
+    }
+    fn get_raw_count(&mut self) -> Option<f64> {
+        // `raw_count` is already LogLog-Beta corrected, so estimate from the registers.
+        let registers: Vec<u8> = self.iter().collect();
+        Some(crate::hllpp::raw_estimate(&registers).0)
+    }
     fn init(precision: u8) -> Self {
         hyperloglockless::HyperLogLog::with_hasher(precision, S::default())
     }
     fn name() -> &'static str {
         "hyperloglockless::HyperLogLog"
     }
+    fn capabilities() -> Capabilities {
+        Capabilities {
+            merge: true,
+            raw_count: true,
+            generic_keys: true,
+            shared_count: true,
+            prehashed: true,
+            ..Default::default()
+        }
+    }
+    fn merge(&mut self, other: &Self) {
+        // `union` also counts the registers empty in both sketches as filled, so take the max
+        // of the registers and rebuild the sketch from them instead.
+        assert_eq!(self.len(), other.len(), "same precision");
+        let registers: Box<[u8]> = self
+            .iter()
+            .zip(other.iter())
+            .map(|(a, b)| a.max(b))
+            .collect();
+        let zeros = registers.iter().filter(|&&r| r == 0).count();
+        let sum = registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let hasher = self.parts().1.clone();
+        *self = hyperloglockless::HyperLogLog::from_parts(registers, hasher, zeros, sum);
+    }
 }
 
 impl Container<u64> for hyperloglockless::HyperLogLogPlus<ahash::RandomState> {
@@ -32,15 +240,30 @@ impl Container<u64> for hyperloglockless::HyperLogLogPlus<ahash::RandomState> {
         self.insert(s);
     }
     #[inline]
+    fn put_hash(&mut self, hash: u64) {
+        self.insert_hash(hash);
+    }
+    #[inline]
     fn get_count(&mut self) -> f64 {
         self.raw_count()
     }
     fn init(precision: u8) -> Self {
-        hyperloglockless::HyperLogLogPlus::with_hasher(precision, ahash::RandomState::default())
+        hyperloglockless::HyperLogLogPlus::with_hasher(precision, FixedState::ahash())
     }
     fn name() -> &'static str {
         "hyperloglockless::HyperLogLogPlus"
     }
+    fn is_sparse(&self) -> Option<bool> {
+        Some(hyperloglockless::HyperLogLogPlus::is_sparse(self))
+    }
+    fn capabilities() -> Capabilities {
+        Capabilities {
+            sparse: true,
+            generic_keys: true,
+            prehashed: true,
+            ..Default::default()
+        }
+    }
 }
 
 impl Container<u64> for hyperloglockless::AtomicHyperLogLog<ahash::RandomState> {
@@ -49,15 +272,41 @@ impl Container<u64> for hyperloglockless::AtomicHyperLogLog<ahash::RandomState>
         self.insert(s);
     }
     #[inline]
+    fn put_hash(&mut self, hash: u64) {
+        self.insert_hash(hash);
+    }
+    #[inline]
     fn get_count(&mut self) -> f64 {
         self.raw_count()
     }
+    fn peek_count(&self) -> Option<f64> {
+        Some(self.raw_count())
+    }
+    fn get_raw_count(&mut self) -> Option<f64> {
+        // `raw_count` is already LogLog-Beta corrected, so estimate from the registers.
+        let registers: Vec<u8> = self.iter().collect();
+        Some(crate::hllpp::raw_estimate(&registers).0)
+    }
     fn init(precision: u8) -> Self {
-        hyperloglockless::AtomicHyperLogLog::with_hasher(precision, ahash::RandomState::default())
+        hyperloglockless::AtomicHyperLogLog::with_hasher(precision, FixedState::ahash())
     }
     fn name() -> &'static str {
         "hyperloglockless::AtomicHyperLogLog"
     }
+    fn capabilities() -> Capabilities {
+        Capabilities {
+            concurrent: true,
+            merge: true,
+            raw_count: true,
+            generic_keys: true,
+            shared_count: true,
+            prehashed: true,
+            ..Default::default()
+        }
+    }
+    fn merge(&mut self, other: &Self) {
+        hyperloglockless::AtomicHyperLogLog::union(self, other).expect("same precision");
+    }
 }
 
 impl Container<u64> for hyperloglogplus::HyperLogLogPlus<u64, ahash::RandomState> {
@@ -70,11 +319,22 @@ impl Container<u64> for hyperloglogplus::HyperLogLogPlus<u64, ahash::RandomState
         self.count()
     }
     fn init(precision: u8) -> Self {
-        hyperloglogplus::HyperLogLogPlus::new(precision, ahash::RandomState::default()).unwrap()
+        hyperloglogplus::HyperLogLogPlus::new(precision, FixedState::ahash()).unwrap()
     }
     fn name() -> &'static str {
         "hyperloglogplus::HyperLogLogPlus"
     }
+    fn capabilities() -> Capabilities {
+        Capabilities {
+            merge: true,
+            sparse: true,
+            generic_keys: true,
+            ..Default::default()
+        }
+    }
+    fn merge(&mut self, other: &Self) {
+        hyperloglogplus::HyperLogLogPlus::merge(self, other).unwrap();
+    }
 }
 
 impl Container<u64> for hyperloglogplus::HyperLogLogPF<u64, ahash::RandomState> {
@@ -87,11 +347,24 @@ impl Container<u64> for hyperloglogplus::HyperLogLogPF<u64, ahash::RandomState>
         self.count()
     }
     fn init(precision: u8) -> Self {
-        hyperloglogplus::HyperLogLogPF::new(precision, ahash::RandomState::default()).unwrap()
+        hyperloglogplus::HyperLogLogPF::new(precision, FixedState::ahash()).unwrap()
     }
     fn name() -> &'static str {
         "hyperloglogplus::HyperLogLogPF"
     }
+    fn capabilities() -> Capabilities {
+        Capabilities {
+            merge: true,
+            generic_keys: true,
+            ..Default::default()
+        }
+    }
+    fn merge(&mut self, other: &Self) {
+        hyperloglogplus::HyperLogLogPF::merge(self, other).unwrap();
+    }
+    fn supports_precision(precision: u8) -> bool {
+        (4..=16).contains(&precision)
+    }
 }
 
 impl Container<u64>
@@ -105,21 +378,74 @@ impl Container<u64>
     fn get_count(&mut self) -> f64 {
         self.len()
     }
+    fn peek_count(&self) -> Option<f64> {
+        Some(self.len())
+    }
     fn init(precision: u8) -> Self {
-        // let p = (1.04 / error_probability).powi(2).ln().ceil() as usize;
-        let err = 1.04 / (2.71f64.powf(precision as f64)).sqrt();
-        let p = (1.04 / err).powi(2).ln().ceil() as usize;
-        assert_eq!(precision, p as u8);
+        assert!(Self::supports_precision(precision));
         probabilistic_collections::hyperloglog::HyperLogLog::<u64, ahash::RandomState>::with_hasher(
-            err,
-            ahash::RandomState::default(),
+            prob_err(precision),
+            FixedState::ahash(),
         )
     }
     fn name() -> &'static str {
         "probabilistic_collections::HyperLogLog"
     }
+    fn capabilities() -> Capabilities {
+        Capabilities {
+            generic_keys: true,
+            shared_count: true,
+            ..Default::default()
+        }
+    }
+    fn supports_precision(precision: u8) -> bool {
+        // The crate asserts 4..=16 even where the error rate round-trips to the precision.
+        (4..=16).contains(&precision) && precision == ln_precision(prob_err(precision))
+    }
+    fn size_params() -> Vec<SizeParam> {
+        error_rate_sweep_within(ln_precision, 4..=16)
+    }
+    fn init_with(param: SizeParam) -> Self {
+        match param {
+            SizeParam::Precision(p) => Self::init(p),
+            SizeParam::ErrorRate(err) => probabilistic_collections::hyperloglog::HyperLogLog::<
+                u64,
+                ahash::RandomState,
+            >::with_hasher(err, FixedState::ahash()),
+        }
+    }
+}
+
+/// Precision probabilistic_collections and hyperloglog pick for `error_rate`. Both panic
+/// outside 4..=16, and hyperloglog also panics counting sparse sketches above 14.
+fn ln_precision(error_rate: f64) -> u8 {
+    (1.04 / error_rate).powi(2).ln().ceil() as u8
+}
+
+/// The [`error_rate_sweep`] that `precision_for` maps to `precisions`.
+fn error_rate_sweep_within(
+    precision_for: impl Fn(f64) -> u8,
+    precisions: std::ops::RangeInclusive<u8>,
+) -> Vec<SizeParam> {
+    error_rate_sweep()
+        .into_iter()
+        .filter(|param| match param {
+            SizeParam::ErrorRate(err) => precisions.contains(&precision_for(*err)),
+            SizeParam::Precision(_) => false,
+        })
+        .collect()
 }
 
+/// Error rate for which probabilistic_collections picks `precision`.
+pub(crate) fn prob_err(precision: u8) -> f64 {
+    1.04 / (2.71f64.powf(precision as f64)).sqrt()
+}
+
+/// The only error rate hyperloglog is benchmarked at, which it maps to p=14. The crate indexes
+/// its linear counting thresholds by precision, so counting a sketch with empty registers
+/// panics above p=14.
+const HYPERLOGLOG_ERROR_RATE: f64 = 0.001;
+
 impl Container<u64> for hyperloglog::HyperLogLog {
     #[inline]
     fn put(&mut self, s: &u64) {
@@ -129,16 +455,35 @@ impl Container<u64> for hyperloglog::HyperLogLog {
     fn get_count(&mut self) -> f64 {
         self.len()
     }
+    fn peek_count(&self) -> Option<f64> {
+        Some(self.len())
+    }
     fn init(precision: u8) -> Self {
-        let error_rate: f64 = 0.0005;
-        let sr = 1.04 / error_rate;
-        let p = f64::ln(sr * sr).ceil() as u8;
-        assert_eq!(precision, p as u8);
-        hyperloglog::HyperLogLog::new(error_rate)
+        assert!(Self::supports_precision(precision));
+        hyperloglog::HyperLogLog::new(HYPERLOGLOG_ERROR_RATE)
     }
     fn name() -> &'static str {
         "hyperloglog::HyperLogLog"
     }
+    fn capabilities() -> Capabilities {
+        Capabilities {
+            generic_keys: true,
+            shared_count: true,
+            ..Default::default()
+        }
+    }
+    fn supports_precision(precision: u8) -> bool {
+        precision == ln_precision(HYPERLOGLOG_ERROR_RATE)
+    }
+    fn size_params() -> Vec<SizeParam> {
+        error_rate_sweep_within(ln_precision, 4..=14)
+    }
+    fn init_with(param: SizeParam) -> Self {
+        match param {
+            SizeParam::Precision(p) => Self::init(p),
+            SizeParam::ErrorRate(err) => hyperloglog::HyperLogLog::new(err),
+        }
+    }
 }
 
 macro_rules! impl_card {
@@ -151,24 +496,68 @@ macro_rules! impl_card {
                 self.insert(s);
             }
             #[inline]
+            fn put_hash(&mut self, hash: u64) {
+                self.insert_hash(hash);
+            }
+            #[inline]
             fn get_count(&mut self) -> f64 {
                 self.estimate() as f64
             }
+            fn peek_count(&self) -> Option<f64> {
+                Some(self.estimate() as f64)
+            }
             fn init(precision: u8) -> Self {
-                assert_eq!(precision, $size);
+                assert!(Self::supports_precision(precision));
                 cardinality_estimator::CardinalityEstimator::<u64, ahash::AHasher, $size, 6>::new()
             }
             fn name() -> &'static str {
                 "cardinality_estimator::CardinalityEstimator"
             }
+            fn capabilities() -> Capabilities {
+                Capabilities {
+                    sparse: true,
+                    generic_keys: true,
+                    shared_count: true,
+                    prehashed: true,
+                    ..Default::default()
+                }
+            }
+            fn supports_precision(precision: u8) -> bool {
+                precision == $size
+            }
+            fn size_params() -> Vec<SizeParam> {
+                // Sized by const generics, so each instantiation only has one size.
+                vec![SizeParam::Precision($size)]
+            }
         }
     };
 }
 
-impl_card!(16);
-impl_card!(14);
-impl_card!(12);
+impl_card!(4);
+impl_card!(5);
+impl_card!(6);
+impl_card!(7);
+impl_card!(8);
+impl_card!(9);
 impl_card!(10);
+impl_card!(11);
+impl_card!(12);
+impl_card!(13);
+impl_card!(14);
+impl_card!(15);
+impl_card!(16);
+impl_card!(17);
+impl_card!(18);
+
+/// Error rate for which amadeus_streaming picks `precision`.
+fn amadeus_err(precision: u8) -> f64 {
+    1.04 / 2.0f64.powf(precision as f64 / 2.0)
+}
+
+/// Precision amadeus_streaming picks for `error_rate`.
+fn amadeus_precision(error_rate: f64) -> u8 {
+    (f64::log2(1.04 / error_rate) * 2.0).ceil() as u8
+}
 
 impl Container<u64> for amadeus_streaming::HyperLogLog<u64> {
     #[inline]
@@ -177,16 +566,38 @@ impl Container<u64> for amadeus_streaming::HyperLogLog<u64> {
     }
     #[inline]
     fn get_count(&mut self) -> f64 {
-        self.len() as f64
+        self.len()
+    }
+    fn peek_count(&self) -> Option<f64> {
+        Some(self.len())
     }
     fn init(precision: u8) -> Self {
-        let err = 1.04 / 2.0f64.powf(precision as f64 / 2.0);
-        assert_eq!((f64::log2(1.04 / err) * 2.0).ceil() as u8, precision);
-        amadeus_streaming::HyperLogLog::<u64>::new(err)
+        assert!(Self::supports_precision(precision));
+        amadeus_streaming::HyperLogLog::<u64>::new(amadeus_err(precision))
     }
     fn name() -> &'static str {
         "amadeus_streaming::HyperLogLog"
     }
+    fn capabilities() -> Capabilities {
+        Capabilities {
+            generic_keys: true,
+            shared_count: true,
+            ..Default::default()
+        }
+    }
+    fn supports_precision(precision: u8) -> bool {
+        // Like probabilistic_collections, the crate asserts 4..=16.
+        (4..=16).contains(&precision) && amadeus_precision(amadeus_err(precision)) == precision
+    }
+    fn size_params() -> Vec<SizeParam> {
+        error_rate_sweep_within(amadeus_precision, 4..=16)
+    }
+    fn init_with(param: SizeParam) -> Self {
+        match param {
+            SizeParam::Precision(p) => Self::init(p),
+            SizeParam::ErrorRate(err) => amadeus_streaming::HyperLogLog::<u64>::new(err),
+        }
+    }
 }
 
 impl Container<u64> for crate::apache_hll::HyperLogLog<u64> {
@@ -195,14 +606,470 @@ impl Container<u64> for crate::apache_hll::HyperLogLog<u64> {
         self.add(s);
     }
     #[inline]
+    fn put_many(&mut self, items: &[u64]) {
+        self.add_all(items);
+    }
+    #[inline]
+    fn put_hash(&mut self, hash: u64) {
+        self.add_hash(hash);
+    }
+    #[inline]
     fn get_count(&mut self) -> f64 {
         self.count() as f64
     }
+    fn peek_count(&self) -> Option<f64> {
+        Some(self.count() as f64)
+    }
+    fn get_raw_count(&mut self) -> Option<f64> {
+        Some(crate::hllpp::raw_estimate(self.as_ref()).0)
+    }
     fn init(precision: u8) -> Self {
-        assert_eq!(precision, 14);
+        assert!(Self::supports_precision(precision));
         crate::apache_hll::HyperLogLog::<u64>::new()
     }
     fn name() -> &'static str {
         "apache_datafusion::HyperLogLog"
     }
+    fn capabilities() -> Capabilities {
+        Capabilities {
+            merge: true,
+            raw_count: true,
+            generic_keys: true,
+            shared_count: true,
+            prehashed: true,
+            ..Default::default()
+        }
+    }
+    fn merge(&mut self, other: &Self) {
+        crate::apache_hll::HyperLogLog::merge(self, other);
+    }
+    fn supports_precision(precision: u8) -> bool {
+        precision == 14
+    }
+    fn size_params() -> Vec<SizeParam> {
+        vec![SizeParam::Precision(14)]
+    }
+}
+
+impl Container<u64> for crate::apache_hll::IncrementalHyperLogLog<u64> {
+    #[inline]
+    fn put(&mut self, s: &u64) {
+        self.add(s);
+    }
+    #[inline]
+    fn put_hash(&mut self, hash: u64) {
+        self.add_hash(hash);
+    }
+    #[inline]
+    fn get_count(&mut self) -> f64 {
+        self.count() as f64
+    }
+    fn peek_count(&self) -> Option<f64> {
+        Some(self.count() as f64)
+    }
+    fn get_raw_count(&mut self) -> Option<f64> {
+        Some(crate::hllpp::raw_estimate(self.as_ref()).0)
+    }
+    fn init(precision: u8) -> Self {
+        assert!(Self::supports_precision(precision));
+        crate::apache_hll::IncrementalHyperLogLog::<u64>::new()
+    }
+    fn name() -> &'static str {
+        "apache_datafusion::IncrementalHyperLogLog"
+    }
+    fn capabilities() -> Capabilities {
+        Capabilities {
+            merge: true,
+            raw_count: true,
+            generic_keys: true,
+            shared_count: true,
+            prehashed: true,
+            ..Default::default()
+        }
+    }
+    fn merge(&mut self, other: &Self) {
+        crate::apache_hll::IncrementalHyperLogLog::merge(self, other);
+    }
+    fn supports_precision(precision: u8) -> bool {
+        precision == 14
+    }
+    fn size_params() -> Vec<SizeParam> {
+        vec![SizeParam::Precision(14)]
+    }
+}
+
+impl<S: BuildHasher + Default> Container<u64> for crate::hllpp::HyperLogLogPlus<S> {
+    #[inline]
+    fn put(&mut self, s: &u64) {
+        self.insert(s);
+    }
+    #[inline]
+    fn put_hash(&mut self, hash: u64) {
+        self.insert_hash(hash);
+    }
+    #[inline]
+    fn get_count(&mut self) -> f64 {
+        self.count()
+    }
+    fn get_raw_count(&mut self) -> Option<f64> {
+        Some(self.raw_count())
+    }
+    fn init(precision: u8) -> Self {
+        crate::hllpp::HyperLogLogPlus::new(precision)
+    }
+    fn name() -> &'static str {
+        "hllpp::HyperLogLogPlus"
+    }
+    fn is_sparse(&self) -> Option<bool> {
+        Some(crate::hllpp::HyperLogLogPlus::is_sparse(self))
+    }
+    fn capabilities() -> Capabilities {
+        Capabilities {
+            merge: true,
+            sparse: true,
+            raw_count: true,
+            generic_keys: true,
+            prehashed: true,
+            ..Default::default()
+        }
+    }
+    fn merge(&mut self, other: &Self) {
+        crate::hllpp::HyperLogLogPlus::merge(self, other);
+    }
+    fn supports_precision(precision: u8) -> bool {
+        (crate::hllpp::MIN_PRECISION..=crate::hllpp::MAX_PRECISION).contains(&precision)
+    }
+}
+
+impl<S: BuildHasher + Default> Container<u64> for crate::ull::UltraLogLog<S> {
+    #[inline]
+    fn put(&mut self, s: &u64) {
+        self.insert(s);
+    }
+    #[inline]
+    fn put_hash(&mut self, hash: u64) {
+        self.insert_hash(hash);
+    }
+    #[inline]
+    fn get_count(&mut self) -> f64 {
+        self.count()
+    }
+    fn peek_count(&self) -> Option<f64> {
+        Some(self.count())
+    }
+    fn init(precision: u8) -> Self {
+        crate::ull::UltraLogLog::new(precision)
+    }
+    fn name() -> &'static str {
+        "ull::UltraLogLog"
+    }
+    fn capabilities() -> Capabilities {
+        Capabilities {
+            merge: true,
+            generic_keys: true,
+            shared_count: true,
+            prehashed: true,
+            ..Default::default()
+        }
+    }
+    fn merge(&mut self, other: &Self) {
+        crate::ull::UltraLogLog::merge(self, other);
+    }
+    fn supports_precision(precision: u8) -> bool {
+        (crate::ull::MIN_PRECISION..=crate::ull::MAX_PRECISION).contains(&precision)
+    }
+}
+
+impl<S: BuildHasher + Default> Container<u64> for std::collections::HashSet<u64, S> {
+    #[inline]
+    fn put(&mut self, s: &u64) {
+        self.insert(*s);
+    }
+    #[inline]
+    fn get_count(&mut self) -> f64 {
+        self.len() as f64
+    }
+    fn peek_count(&self) -> Option<f64> {
+        Some(self.len() as f64)
+    }
+    fn init(_precision: u8) -> Self {
+        std::collections::HashSet::with_hasher(S::default())
+    }
+    fn name() -> &'static str {
+        "exact::HashSet"
+    }
+    fn capabilities() -> Capabilities {
+        Capabilities {
+            merge: true,
+            exact: true,
+            shared_count: true,
+            ..Default::default()
+        }
+    }
+    fn merge(&mut self, other: &Self) {
+        self.extend(other.iter().copied());
+    }
+    fn supports_precision(_precision: u8) -> bool {
+        true
+    }
+    fn size_params() -> Vec<SizeParam> {
+        // Exact, so the size only depends on the number of items.
+        vec![SizeParam::Precision(0)]
+    }
+}
+
+impl Container<u64> for crate::exact::SortedVec {
+    #[inline]
+    fn put(&mut self, s: &u64) {
+        self.insert(*s);
+    }
+    #[inline]
+    fn get_count(&mut self) -> f64 {
+        self.len() as f64
+    }
+    fn init(_precision: u8) -> Self {
+        crate::exact::SortedVec::new()
+    }
+    fn name() -> &'static str {
+        "exact::SortedVec"
+    }
+    fn capabilities() -> Capabilities {
+        Capabilities {
+            merge: true,
+            exact: true,
+            ..Default::default()
+        }
+    }
+    fn merge(&mut self, other: &Self) {
+        crate::exact::SortedVec::merge(self, other);
+    }
+    fn supports_precision(_precision: u8) -> bool {
+        true
+    }
+    fn size_params() -> Vec<SizeParam> {
+        vec![SizeParam::Precision(0)]
+    }
+}
+
+/// Runs a generic experiment on an implementation type, see [`visit_all`].
+pub trait ContainerVisitor {
+    fn visit<T: Container<u64>>(&mut self);
+}
+
+/// Calls `visitor` with every implementation in the comparison. Check
+/// [`Container::supports_precision`] and [`Container::capabilities`] to skip the experiments
+/// an implementation doesn't support.
+pub fn visit_all<V: ContainerVisitor>(visitor: &mut V) {
+    visitor.visit::<hyperloglockless::HyperLogLog<FixedState>>();
+    visitor.visit::<hyperloglockless::AtomicHyperLogLog<ahash::RandomState>>();
+    visitor.visit::<hyperloglockless::HyperLogLogPlus<ahash::RandomState>>();
+
+    visitor.visit::<hyperloglogplus::HyperLogLogPF<u64, ahash::RandomState>>();
+    visitor.visit::<hyperloglogplus::HyperLogLogPlus<u64, ahash::RandomState>>();
+
+    visitor.visit::<probabilistic_collections::hyperloglog::HyperLogLog<u64, ahash::RandomState>>();
+    // Sized by const generics, one type per precision.
+    visitor.visit::<cardinality_estimator::CardinalityEstimator<u64, ahash::AHasher, 4, 6>>();
+    visitor.visit::<cardinality_estimator::CardinalityEstimator<u64, ahash::AHasher, 5, 6>>();
+    visitor.visit::<cardinality_estimator::CardinalityEstimator<u64, ahash::AHasher, 6, 6>>();
+    visitor.visit::<cardinality_estimator::CardinalityEstimator<u64, ahash::AHasher, 7, 6>>();
+    visitor.visit::<cardinality_estimator::CardinalityEstimator<u64, ahash::AHasher, 8, 6>>();
+    visitor.visit::<cardinality_estimator::CardinalityEstimator<u64, ahash::AHasher, 9, 6>>();
+    visitor.visit::<cardinality_estimator::CardinalityEstimator<u64, ahash::AHasher, 10, 6>>();
+    visitor.visit::<cardinality_estimator::CardinalityEstimator<u64, ahash::AHasher, 11, 6>>();
+    visitor.visit::<cardinality_estimator::CardinalityEstimator<u64, ahash::AHasher, 12, 6>>();
+    visitor.visit::<cardinality_estimator::CardinalityEstimator<u64, ahash::AHasher, 13, 6>>();
+    visitor.visit::<cardinality_estimator::CardinalityEstimator<u64, ahash::AHasher, 14, 6>>();
+    visitor.visit::<cardinality_estimator::CardinalityEstimator<u64, ahash::AHasher, 15, 6>>();
+    visitor.visit::<cardinality_estimator::CardinalityEstimator<u64, ahash::AHasher, 16, 6>>();
+    visitor.visit::<cardinality_estimator::CardinalityEstimator<u64, ahash::AHasher, 17, 6>>();
+    visitor.visit::<cardinality_estimator::CardinalityEstimator<u64, ahash::AHasher, 18, 6>>();
+    visitor.visit::<amadeus_streaming::HyperLogLog<u64>>();
+    visitor.visit::<hyperloglog::HyperLogLog>();
+
+    visitor.visit::<crate::apache_hll::HyperLogLog<u64>>();
+    visitor.visit::<crate::apache_hll::IncrementalHyperLogLog<u64>>();
+    visitor.visit::<crate::hllpp::HyperLogLogPlus<FixedState>>();
+    visitor.visit::<crate::ull::UltraLogLog<FixedState>>();
+
+    visitor.visit::<std::collections::HashSet<u64, FixedState>>();
+    visitor.visit::<crate::exact::SortedVec>();
+}
+
+/// Whether any implementation named `name` supports `precision`. Some libraries have one type
+/// per precision under the same name, so a type that doesn't support `precision` may be
+/// covered by another.
+pub fn name_supports_precision(name: &str, precision: u8) -> bool {
+    struct Supports<'a> {
+        name: &'a str,
+        precision: u8,
+        found: bool,
+    }
+
+    impl ContainerVisitor for Supports<'_> {
+        fn visit<T: Container<u64>>(&mut self) {
+            self.found |= T::name() == self.name && T::supports_precision(self.precision);
+        }
+    }
+
+    let mut visitor = Supports {
+        name,
+        precision,
+        found: false,
+    };
+    visit_all(&mut visitor);
+    visitor.found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merged_matches_union<T: Container<u64>>(precision: u8) {
+        assert!(T::capabilities().merge);
+        let mut a = T::init(precision);
+        let mut b = T::init(precision);
+        let mut all = T::init(precision);
+        for x in 0..20_000u64 {
+            if x % 3 == 0 {
+                b.put(&x);
+            } else {
+                a.put(&x);
+            }
+            all.put(&x);
+        }
+        a.merge(&b);
+        assert_eq!(a.get_count(), all.get_count(), "{}", T::name());
+    }
+
+    #[test]
+    fn test_merge() {
+        merged_matches_union::<crate::apache_hll::HyperLogLog<u64>>(14);
+        merged_matches_union::<crate::apache_hll::IncrementalHyperLogLog<u64>>(14);
+        merged_matches_union::<hyperloglockless::HyperLogLog<FixedState>>(12);
+        merged_matches_union::<hyperloglockless::AtomicHyperLogLog<ahash::RandomState>>(12);
+        merged_matches_union::<crate::hllpp::HyperLogLogPlus<FixedState>>(12);
+        merged_matches_union::<crate::ull::UltraLogLog<FixedState>>(12);
+        merged_matches_union::<std::collections::HashSet<u64, FixedState>>(0);
+        merged_matches_union::<crate::exact::SortedVec>(0);
+    }
+
+    fn prehashed_matches_put<T: Container<u64>>(precision: u8, hash: impl Fn(&u64) -> u64) {
+        assert!(T::capabilities().prehashed);
+        let mut hll = T::init(precision);
+        let mut prehashed = T::init(precision);
+        for x in 0..20_000u64 {
+            hll.put(&x);
+            prehashed.put_hash(hash(&x));
+        }
+        assert_eq!(hll.get_count(), prehashed.get_count(), "{}", T::name());
+    }
+
+    #[test]
+    fn test_put_hash() {
+        let hash = |x: &u64| FixedState.hash_one(x);
+        prehashed_matches_put::<crate::hllpp::HyperLogLogPlus<FixedState>>(12, hash);
+        prehashed_matches_put::<crate::ull::UltraLogLog<FixedState>>(12, hash);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_put_hash_unsupported() {
+        let mut hll = <amadeus_streaming::HyperLogLog<u64> as Container<u64>>::init(12);
+        Container::put_hash(&mut hll, 1);
+    }
+
+    struct PeekCount;
+
+    impl ContainerVisitor for PeekCount {
+        fn visit<T: Container<u64>>(&mut self) {
+            let mut hll = T::init_with(T::size_params()[0]);
+            for x in 0..5_000u64 {
+                hll.put(&x);
+            }
+            let peeked = hll.peek_count();
+            assert_eq!(
+                peeked.is_some(),
+                T::capabilities().shared_count,
+                "{}",
+                T::name()
+            );
+            if let Some(count) = peeked {
+                assert_eq!(count, hll.get_count(), "{}", T::name());
+            }
+        }
+    }
+
+    /// Raw estimates are offered as declared by [`Capabilities::raw_count`], and are close to
+    /// the truth where no small-range correction applies.
+    struct RawCount;
+
+    impl ContainerVisitor for RawCount {
+        fn visit<T: Container<u64>>(&mut self) {
+            if !T::supports_precision(12) {
+                return;
+            }
+            let mut hll = T::init(12);
+            for x in 0..100_000 {
+                hll.put(&x);
+            }
+            let raw = hll.get_raw_count();
+            assert_eq!(raw.is_some(), T::capabilities().raw_count, "{}", T::name());
+            if let Some(raw) = raw {
+                assert!((raw / 100_000.0 - 1.0).abs() < 0.1, "{} {}", T::name(), raw);
+            }
+        }
+    }
+
+    #[test]
+    fn test_raw_count() {
+        visit_all(&mut RawCount);
+    }
+
+    #[test]
+    fn test_peek_count() {
+        visit_all(&mut PeekCount);
+    }
+
+    /// Inits and counts every implementation at every precision of the sweeps it claims to
+    /// support, and at each of its size parameters.
+    struct InitSupported;
+
+    impl ContainerVisitor for InitSupported {
+        fn visit<T: Container<u64>>(&mut self) {
+            let count = |mut hll: T| {
+                hll.put(&1);
+                hll.get_count()
+            };
+            for precision in 4..=18 {
+                if T::supports_precision(precision) {
+                    count(T::init(precision));
+                }
+            }
+            for param in T::size_params() {
+                count(T::init_with(param));
+            }
+        }
+    }
+
+    #[test]
+    fn test_init_supported() {
+        visit_all(&mut InitSupported);
+    }
+
+    #[test]
+    fn test_name_supports_precision() {
+        let name = "cardinality_estimator::CardinalityEstimator";
+        assert!((4..=18).all(|p| name_supports_precision(name, p)));
+        assert!(!name_supports_precision(name, 19));
+        assert!(!name_supports_precision("hyperloglog::HyperLogLog", 18));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_merge_unsupported() {
+        let mut hll = <amadeus_streaming::HyperLogLog<u64> as Container<u64>>::init(12);
+        let other = <amadeus_streaming::HyperLogLog<u64> as Container<u64>>::init(12);
+        Container::merge(&mut hll, &other);
+    }
 }
@@ -0,0 +1,256 @@
+//! Cardinality estimators over a dense array of HLL registers.
+//!
+//! Every estimator takes the same `2^p` registers, each holding the rank of the first 1 bit
+//! after the `p` index bits of a 64-bit hash (0 if empty, at most `65 - p`). Running all of them
+//! on one register array separates the quality of the estimator from differences in hashing and
+//! register updates between implementations.
+
+use crate::apache_hll::{hll_sigma, hll_tau};
+use crate::hllpp;
+
+/// A named estimator, returning `None` for register counts it has no constants for.
+#[derive(Clone, Copy)]
+pub struct Estimator {
+    pub name: &'static str,
+    pub estimate: fn(&[u8]) -> Option<f64>,
+}
+
+pub const ESTIMATORS: [Estimator; 5] = [
+    Estimator {
+        name: "classic",
+        estimate: |r| Some(classic(r)),
+    },
+    Estimator {
+        name: "loglog_beta",
+        estimate: loglog_beta,
+    },
+    Estimator {
+        name: "hllpp",
+        estimate: hllpp_bias_corrected,
+    },
+    Estimator {
+        name: "ertl_improved",
+        estimate: |r| Some(ertl_improved(r)),
+    },
+    Estimator {
+        name: "ertl_ml",
+        estimate: |r| Some(ertl_ml(r)),
+    },
+];
+
+/// Coefficients of the LogLog-Beta correction for `p = 14`, from Qin et al. as used by Redis.
+const LOGLOG_BETA_14: [f64; 8] = [
+    -0.370393911,
+    0.070471823,
+    0.17393686,
+    0.16339839,
+    -0.09237745,
+    0.03738027,
+    -0.005384159,
+    0.00042419,
+];
+
+/// Number of index bits and the largest rank, `q + 1`.
+#[inline]
+fn precision_and_max_rank(registers: &[u8]) -> (u32, usize) {
+    let p = registers.len().trailing_zeros();
+    (p, 65 - p as usize)
+}
+
+/// The original HyperLogLog estimate from Flajolet et al., with linear counting in the small
+/// range and the large range correction adapted to 64-bit hashes.
+pub fn classic(registers: &[u8]) -> f64 {
+    let m = registers.len() as f64;
+    let (raw, zeros) = hllpp::raw_estimate(registers);
+    if raw <= 2.5 * m && zeros > 0 {
+        return hllpp::linear_counting(m, zeros as f64);
+    }
+    let two_64 = 2.0f64.powi(64);
+    if raw > two_64 / 30.0 {
+        -two_64 * (1.0 - raw / two_64).ln()
+    } else {
+        raw
+    }
+}
+
+/// LogLog-Beta from "LogLog-Beta and More: A New Algorithm for Cardinality Estimation Based on
+/// LogLog Counting", Qin et al. Only the `p = 14` coefficients are published.
+pub fn loglog_beta(registers: &[u8]) -> Option<f64> {
+    if registers.len() != 1 << 14 {
+        return None;
+    }
+    let m = registers.len() as f64;
+    let mut sum = 0.0;
+    let mut zeros = 0.0;
+    for &r in registers {
+        sum += 2.0f64.powi(-(r as i32));
+        zeros += (r == 0) as u8 as f64;
+    }
+    let zl = (zeros + 1.0).ln();
+    let beta = LOGLOG_BETA_14[0] * zeros
+        + LOGLOG_BETA_14[1..]
+            .iter()
+            .enumerate()
+            .map(|(i, c)| c * zl.powi(i as i32 + 1))
+            .sum::<f64>();
+    let alpha = 0.7213 / (1.0 + 1.079 / m);
+    Some(alpha * m * (m - zeros) / (beta + sum))
+}
+
+/// The HyperLogLog++ estimate from Heule et al.: empirical bias correction and linear counting
+/// below a per-precision threshold. Only `p` in `4..=18` has bias data.
+pub fn hllpp_bias_corrected(registers: &[u8]) -> Option<f64> {
+    let (p, _) = precision_and_max_rank(registers);
+    let p = p as u8;
+    (hllpp::MIN_PRECISION..=hllpp::MAX_PRECISION)
+        .contains(&p)
+        .then(|| hllpp::dense_estimate(registers, p))
+}
+
+/// The improved estimator from "New cardinality estimation algorithms for HyperLogLog
+/// sketches", Otmar Ertl, which is what [`crate::apache_hll::HyperLogLog::count`] uses.
+pub fn ertl_improved(registers: &[u8]) -> f64 {
+    let (_, max_rank) = precision_and_max_rank(registers);
+    let m = registers.len() as f64;
+    let mut histogram = vec![0u32; max_rank + 1];
+    for &r in registers {
+        histogram[r as usize] += 1;
+    }
+    let mut z = m * hll_tau((m - histogram[max_rank] as f64) / m);
+    for c in histogram[1..max_rank].iter().rev() {
+        z += *c as f64;
+        z *= 0.5;
+    }
+    z += m * hll_sigma(histogram[0] as f64 / m);
+    0.5 / 2_f64.ln() * m * m / z
+}
+
+/// The maximum-likelihood estimate from the same paper as [`ertl_improved`]. The small
+/// `O(1/m)` bias is not corrected.
+pub fn ertl_ml(registers: &[u8]) -> f64 {
+    // A register with rank k < max saw rank k and none above it, the largest rank only
+    // says that rank max - 1 or above was seen.
+    let (_, max_rank) = precision_and_max_rank(registers);
+    let mut unseen = 0.0f64;
+    let mut seen = [0u32; 64];
+    for &r in registers {
+        let r = r as usize;
+        if r < max_rank {
+            unseen += 2.0f64.powi(-(r as i32));
+        }
+        if r > 0 {
+            seen[r.min(max_rank - 1)] += 1;
+        }
+    }
+    registers.len() as f64 * solve_ml(unseen, &seen)
+}
+
+/// Maximizes the log-likelihood `-unseen * x + sum_j seen[j] * ln(1 - exp(-x * 2^-j))`
+/// over the rate `x`.
+///
+/// The derivative is zero where `f(x) = sum_j seen[j] * 2^-j / (exp(x * 2^-j) - 1) - unseen`
+/// is, which is convex and decreasing, so Newton's method converges monotonically from any
+/// point to the left of the root.
+pub(crate) fn solve_ml(unseen: f64, seen: &[u32; 64]) -> f64 {
+    let total_seen: u32 = seen.iter().sum();
+    if total_seen == 0 {
+        return 0.0;
+    }
+    if unseen == 0.0 {
+        return f64::INFINITY;
+    }
+    // 1 / (e^t - 1) >= 1 / t - 1 / 2, which gives a lower bound for the root.
+    let weighted: f64 = seen
+        .iter()
+        .enumerate()
+        .map(|(j, &c)| c as f64 * 2.0f64.powi(-(j as i32)))
+        .sum();
+    let mut x = total_seen as f64 / (unseen + weighted / 2.0);
+    for _ in 0..100 {
+        let mut f = -unseen;
+        let mut df = 0.0;
+        for (j, &c) in seen.iter().enumerate() {
+            if c == 0 {
+                continue;
+            }
+            let w = 2.0f64.powi(-(j as i32));
+            let t = x * w;
+            if t > 700.0 {
+                continue;
+            }
+            let e = t.exp_m1();
+            let term = w / e;
+            f += c as f64 * term;
+            df -= c as f64 * term * term * (e + 1.0);
+        }
+        let step = f / df;
+        x -= step;
+        if step.abs() <= x * 1e-12 {
+            break;
+        }
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apache_hll::HyperLogLog;
+
+    fn filled(n: u64) -> HyperLogLog<u64> {
+        let mut hll = HyperLogLog::new();
+        for i in 0..n {
+            hll.add(&i);
+        }
+        hll
+    }
+
+    #[test]
+    fn test_empty() {
+        let registers = [0u8; 1 << 14];
+        for estimator in ESTIMATORS {
+            assert_eq!(
+                (estimator.estimate)(&registers),
+                Some(0.0),
+                "{}",
+                estimator.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_ertl_improved_matches_apache() {
+        for n in [10, 1_000, 100_000] {
+            let hll = filled(n);
+            assert_eq!(ertl_improved(hll.as_ref()).round() as usize, hll.count());
+        }
+    }
+
+    #[test]
+    fn test_accuracy() {
+        for n in [100, 10_000, 1_000_000] {
+            let hll = filled(n);
+            for estimator in ESTIMATORS {
+                let estimate = (estimator.estimate)(hll.as_ref()).unwrap();
+                let err = (estimate - n as f64).abs() / n as f64;
+                // 6 standard errors
+                assert!(err < 0.05, "{} got {} for {}", estimator.name, estimate, n);
+            }
+        }
+    }
+
+    #[test]
+    fn test_unsupported_precision() {
+        let registers = [0u8; 1 << 20];
+        assert_eq!(loglog_beta(&registers), None);
+        assert_eq!(hllpp_bias_corrected(&registers), None);
+    }
+
+    #[test]
+    fn test_saturated() {
+        // Every register at the largest rank: only the ML estimate knows it's unbounded.
+        let registers = [51u8; 1 << 14];
+        assert_eq!(ertl_ml(&registers), f64::INFINITY);
+        assert_eq!(ertl_improved(&registers), f64::INFINITY);
+    }
+}
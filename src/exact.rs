@@ -0,0 +1,87 @@
+//! Exact distinct counters, used as the naive baseline that sketches have to beat.
+
+/// Exact set of `u64`s kept as a sorted, deduplicated vector.
+///
+/// New items are appended to an unsorted buffer that is merged into the sorted part once it
+/// grows as large as it, so inserts are amortized O(log n) and memory is at most ~2x the
+/// number of distinct items.
+#[derive(Clone, Debug, Default)]
+pub struct SortedVec {
+    sorted: Vec<u64>,
+    pending: Vec<u64>,
+}
+
+/// Smallest buffer size before merging, so tiny sets don't re-sort on every insert.
+const MIN_PENDING: usize = 1024;
+
+impl SortedVec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, item: u64) {
+        self.pending.push(item);
+        if self.pending.len() >= self.sorted.len().max(MIN_PENDING) {
+            self.flush();
+        }
+    }
+
+    /// Merges the pending buffer into the sorted part.
+    pub fn flush(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        // The stable sort detects the already sorted prefix, so this is a sort of the
+        // pending items followed by a linear merge.
+        self.sorted.append(&mut self.pending);
+        self.sorted.sort();
+        self.sorted.dedup();
+    }
+
+    /// Adds all items of `other`.
+    pub fn merge(&mut self, other: &SortedVec) {
+        self.pending.extend_from_slice(&other.sorted);
+        self.pending.extend_from_slice(&other.pending);
+        self.flush();
+    }
+
+    /// Number of distinct items, merging any pending items first.
+    pub fn len(&mut self) -> usize {
+        self.flush();
+        self.sorted.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sorted.is_empty() && self.pending.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SortedVec;
+
+    #[test]
+    fn test_sorted_vec_dedups() {
+        let mut set = SortedVec::new();
+        assert!(set.is_empty());
+        for i in 0..100_000u64 {
+            set.insert(i % 50_000);
+            set.insert(i * 31 % 50_000);
+        }
+        assert_eq!(set.len(), 50_000);
+        set.insert(u64::MAX);
+        assert_eq!(set.len(), 50_001);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut a = SortedVec::new();
+        let mut b = SortedVec::new();
+        for i in 0..3_000u64 {
+            a.insert(i);
+            b.insert(i + 2_000);
+        }
+        a.merge(&b);
+        assert_eq!(a.len(), 5_000);
+    }
+}
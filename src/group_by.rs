@@ -0,0 +1,177 @@
+//! Many small sketches: the `SELECT group, COUNT(DISTINCT item) ... GROUP BY group` workload.
+//!
+//! Rows are `(group, item)` pairs with Zipf distributed groups and items, so a few groups get
+//! most rows and the long tail sees only a handful of items each. Every group that occurs
+//! gets its own sketch in a hash map, so the footprint of a nearly empty sketch, not its
+//! accuracy at scale, dominates the memory.
+
+use crate::{Container, ErrStats};
+use rand::SeedableRng;
+use rand::distributions::Distribution;
+use rand::rngs::StdRng;
+use rand_distr::Zipf;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Instant;
+
+/// The rows of one workload, and the exact distinct count of each group.
+pub struct Workload {
+    pub num_groups: u64,
+    pub rows: Vec<(u64, u64)>,
+    /// `(group, distinct items)` of every group that occurs, sorted by group.
+    pub truth: Vec<(u64, u64)>,
+}
+
+impl Workload {
+    /// `num_rows` rows with groups drawn from `Zipf(num_groups, group_exponent)` and items from
+    /// `Zipf(num_items, item_exponent)`.
+    pub fn new(
+        num_groups: u64,
+        num_rows: usize,
+        num_items: u64,
+        group_exponent: f64,
+        item_exponent: f64,
+        seed: u64,
+    ) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let groups = Zipf::new(num_groups, group_exponent).unwrap();
+        let items = Zipf::new(num_items, item_exponent).unwrap();
+        let rows: Vec<(u64, u64)> = (0..num_rows)
+            .map(|_| {
+                let group: f64 = groups.sample(&mut rng);
+                let item: f64 = items.sample(&mut rng);
+                (group as u64, item as u64)
+            })
+            .collect();
+
+        let mut distinct = rows.clone();
+        distinct.sort_unstable();
+        distinct.dedup();
+        let mut truth: Vec<(u64, u64)> = Vec::new();
+        for (group, _) in distinct {
+            match truth.last_mut() {
+                Some((g, count)) if *g == group => *count += 1,
+                _ => truth.push((group, 1)),
+            }
+        }
+        Self {
+            num_groups,
+            rows,
+            truth,
+        }
+    }
+}
+
+/// Heap bytes of one empty sketch, a lower bound for the footprint of each group.
+pub fn empty_bytes<T: Container<u64>>(precision: u8) -> usize {
+    let (hll, heap) = crate::alloc::measure(|| T::init(precision));
+    drop(hll);
+    std::mem::size_of::<T>() + heap.max(0) as usize
+}
+
+/// Results of one implementation on one [`Workload`].
+pub struct GroupByRow {
+    pub num_groups: u64,
+    /// Groups that occur in the rows, and so have a sketch.
+    pub present_groups: usize,
+    pub num_rows: usize,
+    /// Heap bytes of the map and all its sketches.
+    pub bytes: usize,
+    pub ns_per_row: f64,
+    /// Relative error of the count of each group.
+    pub err: ErrStats,
+    /// Root mean square of the relative errors.
+    pub rmse: f64,
+}
+
+impl fmt::Display for GroupByRow {
+    /// `num_groups,present_groups,num_rows,bytes,bytes_per_group,ns_per_row,avg,min,max,rmse`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{},{},{},{},{},{},{},{},{},{}",
+            self.num_groups,
+            self.present_groups,
+            self.num_rows,
+            self.bytes,
+            self.bytes as f64 / self.present_groups as f64,
+            self.ns_per_row,
+            self.err.avg,
+            self.err.min,
+            self.err.max,
+            self.rmse
+        )
+    }
+}
+
+/// Inserts every row of `workload` into a map of one `T` per group, then counts every group.
+pub fn group_by<T: Container<u64>>(workload: &Workload, precision: u8) -> GroupByRow {
+    let ((mut map, ns_per_row), bytes) = crate::alloc::measure(|| {
+        let mut map: HashMap<u64, T, ahash::RandomState> = HashMap::default();
+        let now = Instant::now();
+        for (group, item) in workload.rows.iter() {
+            map.entry(*group)
+                .or_insert_with(|| T::init(precision))
+                .put(item);
+        }
+        let ns = now.elapsed().as_nanos() as f64 / workload.rows.len() as f64;
+        (map, ns)
+    });
+
+    let errs: Vec<f64> = workload
+        .truth
+        .iter()
+        .map(|(group, real)| {
+            let real = *real as f64;
+            (map.get_mut(group).unwrap().get_count() - real).abs() / real
+        })
+        .collect();
+    let rmse = (errs.iter().map(|e| e * e).sum::<f64>() / errs.len() as f64).sqrt();
+    GroupByRow {
+        num_groups: workload.num_groups,
+        present_groups: map.len(),
+        num_rows: workload.rows.len(),
+        bytes: bytes.max(0) as usize,
+        ns_per_row,
+        err: ErrStats::new(errs.into_iter()),
+        rmse,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truth() {
+        let workload = Workload::new(100, 10_000, 1_000, 1.0, 1.0, 0);
+        assert!(
+            workload
+                .rows
+                .iter()
+                .all(|&(g, i)| (1..=100).contains(&g) && i <= 1_000)
+        );
+        let mut truth: HashMap<u64, std::collections::HashSet<u64>> = HashMap::new();
+        for &(g, i) in workload.rows.iter() {
+            truth.entry(g).or_default().insert(i);
+        }
+        assert_eq!(workload.truth.len(), truth.len());
+        for (g, count) in workload.truth.iter() {
+            assert_eq!(*count as usize, truth[g].len());
+        }
+        // Zipf: the first group gets the most rows.
+        assert_eq!(workload.truth.iter().max_by_key(|t| t.1).unwrap().0, 1);
+    }
+
+    #[test]
+    fn test_group_by() {
+        let workload = Workload::new(1_000, 20_000, 10_000, 1.0, 1.0, 1);
+        let exact = group_by::<std::collections::HashSet<u64, ahash::RandomState>>(&workload, 0);
+        assert_eq!(exact.present_groups, workload.truth.len());
+        assert_eq!(exact.err.max, 0.0);
+
+        let apache = group_by::<crate::apache_hll::HyperLogLog<u64>>(&workload, 14);
+        assert_eq!(apache.present_groups, workload.truth.len());
+        assert!(apache.rmse < 0.05, "{}", apache.rmse);
+    }
+}
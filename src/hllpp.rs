@@ -0,0 +1,525 @@
+//! # HyperLogLog++
+//!
+//! A reference implementation of "HyperLogLog in Practice: Algorithmic Engineering of a
+//! State of The Art Cardinality Estimation Algorithm", Heule, Nunkesser and Hall (EDBT 2013),
+//! written to follow the paper as closely as possible so it can serve as the textbook
+//! baseline in accuracy plots:
+//!
+//! - 64-bit hashes, precisions 4 to 18, one `u8` per register.
+//! - Empirical bias correction (k-nearest-neighbor interpolation over the paper's tables)
+//!   for raw estimates up to `5m`.
+//! - Linear counting below the paper's empirical thresholds.
+//! - A sparse representation with precision `p' = 25` using the paper's hash encoding, kept
+//!   as a sorted list plus an unsorted buffer. The list is stored as plain `u32`s instead of
+//!   the paper's difference/varint encoding, so it converts to dense earlier (once it would
+//!   use as many bytes as the registers).
+
+use std::hash::{BuildHasher, Hash};
+
+mod bias_data;
+use bias_data::{BIAS_DATA, RAW_ESTIMATE_DATA};
+
+pub const MIN_PRECISION: u8 = 4;
+pub const MAX_PRECISION: u8 = 18;
+
+/// Precision of the sparse representation, `p'` in the paper.
+const SPARSE_PRECISION: u32 = 25;
+
+/// Estimates below these use linear counting, for precisions 4 to 18.
+const THRESHOLDS: [f64; 15] = [
+    10.0, 20.0, 40.0, 80.0, 220.0, 400.0, 900.0, 1800.0, 3100.0, 6500.0, 11500.0, 20000.0, 50000.0,
+    120000.0, 350000.0,
+];
+
+/// Number of nearest raw estimates averaged to estimate the bias.
+const BIAS_NEIGHBORS: usize = 6;
+
+#[derive(Clone, Debug)]
+enum Repr {
+    /// Sorted encoded hashes, unique by sparse index, plus not yet merged ones.
+    Sparse {
+        list: Vec<u32>,
+        buffer: Vec<u32>,
+    },
+    Dense(Vec<u8>),
+}
+
+#[derive(Clone, Debug)]
+pub struct HyperLogLogPlus<S = ahash::RandomState> {
+    precision: u8,
+    hasher: S,
+    repr: Repr,
+}
+
+impl<S: BuildHasher + Default> HyperLogLogPlus<S> {
+    /// Creates a new, empty, sparse HyperLogLog++ with `2^precision` registers.
+    pub fn new(precision: u8) -> Self {
+        Self::with_hasher(precision, S::default())
+    }
+}
+
+impl<S: BuildHasher> HyperLogLogPlus<S> {
+    /// Creates a new, empty, sparse HyperLogLog++ with `2^precision` registers.
+    ///
+    /// # Panics
+    /// If `precision` is outside of `4..=18`, the range the bias tables cover.
+    pub fn with_hasher(precision: u8, hasher: S) -> Self {
+        assert!(
+            (MIN_PRECISION..=MAX_PRECISION).contains(&precision),
+            "precision must be in {}..={}, got {}",
+            MIN_PRECISION,
+            MAX_PRECISION,
+            precision
+        );
+        Self {
+            precision,
+            hasher,
+            repr: Repr::Sparse {
+                list: Vec::new(),
+                buffer: Vec::new(),
+            },
+        }
+    }
+
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+
+    #[inline]
+    fn num_registers(&self) -> usize {
+        1 << self.precision
+    }
+
+    /// Max number of sparse entries before converting to dense: the point at which the
+    /// sparse list would take as many bytes as the registers.
+    #[inline]
+    fn sparse_limit(&self) -> usize {
+        self.num_registers() / size_of::<u32>()
+    }
+
+    pub fn is_sparse(&self) -> bool {
+        matches!(self.repr, Repr::Sparse { .. })
+    }
+
+    /// The registers, if in dense representation.
+    pub fn registers(&self) -> Option<&[u8]> {
+        match &self.repr {
+            Repr::Dense(registers) => Some(registers),
+            Repr::Sparse { .. } => None,
+        }
+    }
+
+    /// Adds an element to the HyperLogLog++.
+    #[inline]
+    pub fn insert<T: Hash + ?Sized>(&mut self, obj: &T) {
+        let hash = self.hasher.hash_one(obj);
+        self.insert_hash(hash);
+    }
+
+    /// Adds an already hashed element to the HyperLogLog++.
+    #[inline]
+    pub fn insert_hash(&mut self, hash: u64) {
+        let sparse_limit = self.sparse_limit();
+        match &mut self.repr {
+            Repr::Dense(registers) => {
+                let (index, rank) = dense_index_rank(hash, self.precision);
+                registers[index] = registers[index].max(rank);
+            }
+            Repr::Sparse { buffer, .. } => {
+                buffer.push(encode_hash(hash, self.precision));
+                if buffer.len() * 4 >= sparse_limit {
+                    self.flush();
+                }
+            }
+        }
+    }
+
+    /// Merges the sparse buffer into the sorted list, converting to dense if the list grows
+    /// too large.
+    fn flush(&mut self) {
+        let sparse_limit = self.sparse_limit();
+        let Repr::Sparse { list, buffer } = &mut self.repr else {
+            return;
+        };
+        if buffer.is_empty() {
+            return;
+        }
+        sort_sparse(buffer);
+        *list = merge_sparse(list, buffer);
+        buffer.clear();
+        if list.len() > sparse_limit {
+            self.convert_to_dense();
+        }
+    }
+
+    fn convert_to_dense(&mut self) {
+        let Repr::Sparse { list, buffer } = &self.repr else {
+            return;
+        };
+        let mut registers = vec![0u8; self.num_registers()];
+        for &code in list.iter().chain(buffer.iter()) {
+            let (index, rank) = decode_hash(code, self.precision);
+            registers[index] = registers[index].max(rank);
+        }
+        self.repr = Repr::Dense(registers);
+    }
+
+    /// Merge the other [`HyperLogLogPlus`] into this one.
+    ///
+    /// # Panics
+    /// If the precisions differ.
+    pub fn merge<T: BuildHasher>(&mut self, other: &HyperLogLogPlus<T>) {
+        assert_eq!(
+            self.precision, other.precision,
+            "unexpected got unequal precisions"
+        );
+        match (&mut self.repr, &other.repr) {
+            (
+                Repr::Sparse { buffer, .. },
+                Repr::Sparse {
+                    list,
+                    buffer: other_buffer,
+                },
+            ) => {
+                buffer.extend_from_slice(list);
+                buffer.extend_from_slice(other_buffer);
+                self.flush();
+            }
+            (_, Repr::Sparse { list, buffer }) => {
+                let Repr::Dense(registers) = &mut self.repr else {
+                    unreachable!()
+                };
+                for &code in list.iter().chain(buffer.iter()) {
+                    let (index, rank) = decode_hash(code, self.precision);
+                    registers[index] = registers[index].max(rank);
+                }
+            }
+            (_, Repr::Dense(other_registers)) => {
+                self.convert_to_dense();
+                let Repr::Dense(registers) = &mut self.repr else {
+                    unreachable!()
+                };
+                for (r, o) in registers.iter_mut().zip(other_registers) {
+                    *r = (*r).max(*o);
+                }
+            }
+        }
+    }
+
+    /// Guess the number of unique elements seen by the HyperLogLog++.
+    pub fn count(&mut self) -> f64 {
+        self.flush();
+        match &self.repr {
+            Repr::Sparse { list, .. } => {
+                let m = (1u64 << SPARSE_PRECISION) as f64;
+                linear_counting(m, m - list.len() as f64)
+            }
+            Repr::Dense(registers) => dense_estimate(registers, self.precision),
+        }
+    }
+
+    /// The raw HyperLogLog estimate `alpha * m^2 / sum(2^-M[j])`, with no bias correction or
+    /// linear counting. Sparse entries are folded into `2^p` registers first.
+    pub fn raw_count(&mut self) -> f64 {
+        self.flush();
+        match &self.repr {
+            Repr::Dense(registers) => raw_estimate(registers).0,
+            Repr::Sparse { list, .. } => {
+                let mut registers = vec![0u8; self.num_registers()];
+                for &code in list {
+                    let (index, rank) = decode_hash(code, self.precision);
+                    registers[index] = registers[index].max(rank);
+                }
+                raw_estimate(&registers).0
+            }
+        }
+    }
+}
+
+/// Register index (the top `p` bits) and rank (position of the first 1 bit after them).
+#[inline]
+fn dense_index_rank(hash: u64, precision: u8) -> (usize, u8) {
+    let index = (hash >> (64 - precision)) as usize;
+    let rank = ((hash << precision) | (1 << (precision - 1))).leading_zeros() + 1;
+    (index, rank as u8)
+}
+
+/// Encodes a hash for the sparse representation (Figure 7 of the paper).
+///
+/// The top `p'` bits are kept as the sparse index. If the bits between `p` and `p'` are all
+/// zero, the rank can't be recovered from the index, so it's stored too and the low bit is
+/// set.
+#[inline]
+fn encode_hash(hash: u64, precision: u8) -> u32 {
+    let sparse_index = (hash >> (64 - SPARSE_PRECISION)) as u32;
+    let between_mask = (1 << (SPARSE_PRECISION - precision as u32)) - 1;
+    if sparse_index & between_mask == 0 {
+        let rank = ((hash << SPARSE_PRECISION) | (1 << (SPARSE_PRECISION - 1))).leading_zeros() + 1;
+        (sparse_index << 7) | (rank << 1) | 1
+    } else {
+        sparse_index << 1
+    }
+}
+
+/// Sparse index (the top `p'` bits of the hash) of an encoded hash.
+#[inline]
+fn sparse_index(code: u32) -> u32 {
+    if code & 1 == 1 { code >> 7 } else { code >> 1 }
+}
+
+/// Decodes a sparse entry into the register index and rank it represents at precision `p`.
+#[inline]
+fn decode_hash(code: u32, precision: u8) -> (usize, u8) {
+    let p = precision as u32;
+    let sparse_index = sparse_index(code);
+    let index = (sparse_index >> (SPARSE_PRECISION - p)) as usize;
+    let rank = if code & 1 == 1 {
+        ((code >> 1) & 0x3f) + (SPARSE_PRECISION - p)
+    } else {
+        // The first 1 bit after the top `p` bits of the sparse index.
+        (sparse_index << (32 - SPARSE_PRECISION + p)).leading_zeros() + 1
+    };
+    (index, rank as u8)
+}
+
+/// Sorts by sparse index and keeps the highest rank for each.
+fn sort_sparse(codes: &mut Vec<u32>) {
+    codes.sort_unstable_by_key(|&code| (sparse_index(code), std::cmp::Reverse(code)));
+    codes.dedup_by_key(|code| sparse_index(*code));
+}
+
+/// Merges two lists sorted and deduplicated by [`sort_sparse`].
+fn merge_sparse(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut res = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        let (x, y) = (a[i], b[j]);
+        match sparse_index(x).cmp(&sparse_index(y)) {
+            std::cmp::Ordering::Less => {
+                res.push(x);
+                i += 1;
+            }
+            std::cmp::Ordering::Greater => {
+                res.push(y);
+                j += 1;
+            }
+            std::cmp::Ordering::Equal => {
+                // Same sparse index, so a larger code means a larger rank.
+                res.push(x.max(y));
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    res.extend_from_slice(&a[i..]);
+    res.extend_from_slice(&b[j..]);
+    res
+}
+
+/// The bias corrected estimate of `2^precision` dense registers, falling back to linear
+/// counting below the threshold for the precision.
+pub(crate) fn dense_estimate(registers: &[u8], precision: u8) -> f64 {
+    let m = registers.len() as f64;
+    let (raw, zeros) = raw_estimate(registers);
+    let estimate = if raw <= 5.0 * m {
+        raw - estimate_bias(raw, precision)
+    } else {
+        raw
+    };
+    if zeros > 0 {
+        let lc = linear_counting(m, zeros as f64);
+        if lc <= THRESHOLDS[(precision - MIN_PRECISION) as usize] {
+            return lc;
+        }
+    }
+    estimate
+}
+
+#[inline]
+fn alpha(m: f64) -> f64 {
+    match m as usize {
+        16 => 0.673,
+        32 => 0.697,
+        64 => 0.709,
+        _ => 0.7213 / (1.0 + 1.079 / m),
+    }
+}
+
+/// Returns the raw estimate and the number of zero registers.
+pub(crate) fn raw_estimate(registers: &[u8]) -> (f64, usize) {
+    let m = registers.len() as f64;
+    let mut sum = 0.0;
+    let mut zeros = 0;
+    for &r in registers {
+        sum += 2.0f64.powi(-(r as i32));
+        zeros += (r == 0) as usize;
+    }
+    (alpha(m) * m * m / sum, zeros)
+}
+
+#[inline]
+pub(crate) fn linear_counting(m: f64, zeros: f64) -> f64 {
+    m * (m / zeros).ln()
+}
+
+/// Averages the empirical bias of the [`BIAS_NEIGHBORS`] raw estimates closest to `raw`.
+fn estimate_bias(raw: f64, precision: u8) -> f64 {
+    let i = (precision - MIN_PRECISION) as usize;
+    let (estimates, biases) = (RAW_ESTIMATE_DATA[i], BIAS_DATA[i]);
+    let mut nearest = [(f64::MAX, 0.0); BIAS_NEIGHBORS];
+    for (e, b) in estimates.iter().zip(biases) {
+        let dist = (e - raw).abs();
+        if dist < nearest[BIAS_NEIGHBORS - 1].0 {
+            nearest[BIAS_NEIGHBORS - 1] = (dist, *b);
+            nearest.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+        }
+    }
+    nearest.iter().map(|(_, b)| b).sum::<f64>() / BIAS_NEIGHBORS as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ahash::RandomState;
+
+    fn new_hll(precision: u8) -> HyperLogLogPlus<RandomState> {
+        HyperLogLogPlus::with_hasher(precision, RandomState::with_seeds(1, 2, 3, 4))
+    }
+
+    /// Deterministic, well mixed hashes (splitmix64).
+    fn hashes(seed: u64) -> impl Iterator<Item = u64> {
+        let mut state = seed;
+        std::iter::repeat_with(move || {
+            state = state.wrapping_add(0x9E3779B97F4A7C15);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+            z ^ (z >> 31)
+        })
+    }
+
+    fn compare_with_delta(got: f64, expected: usize, precision: u8) {
+        let expected = expected as f64;
+        let diff = (got - expected).abs() / expected;
+        // 6 standard errors, so the tests are stable
+        let margin = 1.04 / ((1u64 << precision) as f64).sqrt() * 6.0;
+        assert!(
+            diff <= margin,
+            "{} is not within {} of {}",
+            got,
+            margin,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_empty() {
+        for p in MIN_PRECISION..=MAX_PRECISION {
+            assert_eq!(new_hll(p).count(), 0.0);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_unsupported_precision() {
+        new_hll(19);
+    }
+
+    #[test]
+    fn test_encode_decode_matches_dense() {
+        for p in MIN_PRECISION..=MAX_PRECISION {
+            let special = [0, u64::MAX, 1, 1 << 63, 1 << (64 - SPARSE_PRECISION)];
+            for hash in hashes(p as u64).take(10_000).chain(special) {
+                let code = encode_hash(hash, p);
+                assert_eq!(decode_hash(code, p), dense_index_rank(hash, p), "{hash:x}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_sparse_small_counts() {
+        let mut hll = new_hll(14);
+        for (i, hash) in hashes(0).take(1000).enumerate() {
+            hll.insert_hash(hash);
+            hll.insert_hash(hash);
+            let n = i as f64 + 1.0;
+            assert!((hll.count() - n).abs() / n < 0.01);
+        }
+        assert!(hll.is_sparse());
+    }
+
+    #[test]
+    fn test_sparse_to_dense_is_lossless() {
+        for p in [4, 10, 14] {
+            let mut hll = new_hll(p);
+            let mut dense = new_hll(p);
+            dense.convert_to_dense();
+            for hash in hashes(7).take(1 << p) {
+                hll.insert_hash(hash);
+                dense.insert_hash(hash);
+            }
+            assert!(!hll.is_sparse());
+            assert_eq!(hll.registers(), dense.registers());
+        }
+    }
+
+    #[test]
+    fn test_accuracy() {
+        for p in [8, 10, 12, 14] {
+            let mut hll = new_hll(p);
+            let mut n = 0;
+            for size in [100, 1_000, 10_000, 100_000, 1_000_000] {
+                for hash in hashes(p as u64).skip(n).take(size - n) {
+                    hll.insert_hash(hash);
+                }
+                n = size;
+                compare_with_delta(hll.count(), n, p);
+            }
+        }
+    }
+
+    #[test]
+    fn test_bias_corrected_range() {
+        // Raw estimates up to 5m are bias corrected.
+        let p = 12;
+        let m = 1 << p;
+        let mut hll = new_hll(p);
+        let mut n = 0;
+        for size in [m, 2 * m, 3 * m, 4 * m, 5 * m] {
+            for hash in hashes(3).skip(n).take(size - n) {
+                hll.insert_hash(hash);
+            }
+            n = size;
+            compare_with_delta(hll.count(), n, p);
+        }
+    }
+
+    #[test]
+    fn test_merge() {
+        for (a_size, b_size) in [(100, 200), (100, 50_000), (50_000, 100), (50_000, 80_000)] {
+            let mut a = new_hll(12);
+            let mut b = new_hll(12);
+            let mut all = new_hll(12);
+            for hash in hashes(1).take(a_size) {
+                a.insert_hash(hash);
+                all.insert_hash(hash);
+            }
+            for hash in hashes(2).take(b_size) {
+                b.insert_hash(hash);
+                all.insert_hash(hash);
+            }
+            a.merge(&b);
+            assert_eq!(a.count(), all.count());
+            assert_eq!(a.is_sparse(), all.is_sparse());
+        }
+    }
+
+    #[test]
+    fn test_raw_count() {
+        let mut hll = new_hll(14);
+        for hash in hashes(5).take(1_000_000) {
+            hll.insert_hash(hash);
+        }
+        compare_with_delta(hll.raw_count(), 1_000_000, 14);
+    }
+}
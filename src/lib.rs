@@ -1,139 +1,405 @@
 use ahash::RandomState;
-use rand::Rng;
 use rayon::prelude::*;
-use std::sync::{LazyLock, RwLock};
+use std::sync::RwLock;
 use std::thread;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 mod container;
-pub use container::Container;
+pub use container::{
+    Capabilities, Container, ContainerVisitor, FixedState, SizeParam, name_supports_precision,
+    visit_all,
+};
 
+pub mod alloc;
 pub mod apache_hll;
+pub mod estimators;
+pub mod exact;
+pub mod group_by;
+pub mod hllpp;
+pub mod sim;
+pub mod sparse;
+pub mod stats;
+pub mod topology;
+pub mod ull;
 
 use hyperloglockless::{AtomicHyperLogLog, HyperLogLog};
-#[allow(unused_imports)]
-use hyperloglogplus::{HyperLogLog as HyperLogLogTrait, HyperLogLogPF, HyperLogLogPlus};
 
-const TOTAL_ITERATIONS: usize = 1_00_000_000;
+/// Inserts and counts in each repetition of the concurrent benchmark.
+const TOTAL_ITERATIONS: usize = 10_000_000;
 const PRECISION: u8 = 10;
 
-#[allow(dead_code)]
-static HYPERLOGLOG: LazyLock<RwLock<HyperLogLogPF<u64, RandomState>>> = LazyLock::new(|| {
-    RwLock::new(HyperLogLogPF::new(PRECISION, RandomState::with_seeds(0, 0, 0, 0)).unwrap())
-});
-
-#[allow(dead_code)]
-static HYPERLOGLOGPLUS: LazyLock<RwLock<HyperLogLogPlus<u64, RandomState>>> = LazyLock::new(|| {
-    RwLock::new(HyperLogLogPlus::new(PRECISION, RandomState::with_seeds(0, 0, 0, 0)).unwrap())
-});
-
-#[allow(dead_code)]
-static CARD: LazyLock<
-    RwLock<cardinality_estimator::CardinalityEstimator<u64, ahash::AHasher, 16, 6>>,
-> = LazyLock::new(|| {
-    RwLock::new(cardinality_estimator::CardinalityEstimator::<
-        u64,
-        ahash::AHasher,
-        16,
-        6,
-    >::new())
-});
-
-#[allow(dead_code)]
-static PROB: LazyLock<
-    RwLock<probabilistic_collections::hyperloglog::HyperLogLog<u64, ahash::RandomState>>,
-> = LazyLock::new(|| {
-    RwLock::new(probabilistic_collections::hyperloglog::HyperLogLog::<
-        u64,
-        ahash::RandomState,
-    >::init(PRECISION))
-});
-
-#[allow(dead_code)]
-static AM: LazyLock<RwLock<amadeus_streaming::HyperLogLog<u64>>> =
-    LazyLock::new(|| RwLock::new(amadeus_streaming::HyperLogLog::<u64>::new(0.005)));
-
-#[allow(dead_code)]
-static LOCKLESS: LazyLock<AtomicHyperLogLog<RandomState>> =
-    LazyLock::new(|| AtomicHyperLogLog::init(PRECISION));
-
-#[allow(dead_code)]
-static LOCKED: LazyLock<RwLock<HyperLogLog<RandomState>>> =
-    LazyLock::new(|| RwLock::new(HyperLogLog::init(PRECISION)));
-
-pub fn insert(val: u64) {
-    //HYPERLOGLOG.write().unwrap().insert(&val);
-    //HYPERLOGLOGPLUS.write().unwrap().insert(&val);
-    //CARD.write().unwrap().insert(&val);
-    //PROB.write().unwrap().insert(&val);
-    //AM.write().unwrap().push(&val);
-    LOCKED.write().unwrap().insert(&val);
-    // LOCKLESS.insert(&val);
-}
-
-pub fn count() -> usize {
-    //HYPERLOGLOG.write().unwrap().count() as usize
-    //HYPERLOGLOGPLUS.write().unwrap().count() as usize
-    //CARD.read().unwrap().estimate() as usize
-    //PROB.read().unwrap().len() as usize
-    //AM.read().unwrap().len() as usize
-    LOCKED.read().unwrap().count() as usize
-    // LOCKLESS.count() as usize
-}
-
-pub fn perf() {
-    perf_inner(16, TOTAL_ITERATIONS);
-}
-
-fn perf_inner(num_threads: usize, total_iterations_count: usize) {
-    let num_iterations = TOTAL_ITERATIONS / num_threads;
-    let num_iterations_count = total_iterations_count / num_threads;
+/// A sketch shared by all threads of the concurrent benchmark.
+pub trait SharedSketch: Sync {
+    fn insert(&self, val: u64);
+    fn count(&self) -> f64;
+    fn name() -> String;
+    /// Whether counting needs exclusive access, blocking inserts and other counts.
+    fn exclusive_count() -> bool;
+}
 
-    let now = Instant::now();
-    let handles: Vec<_> = (0..num_threads)
-        .map(|_| {
-            thread::spawn(move || {
-                let mut rng = rand::thread_rng();
-                for _ in 0..num_iterations {
-                    insert(rng.gen_range(0..u64::MAX));
-                }
-            })
-        })
-        .collect();
+/// Inserts take the write lock. Counts take the read lock if the implementation can count
+/// through [`Container::peek_count`], and the write lock otherwise.
+impl<T: Container<u64> + Send + Sync> SharedSketch for RwLock<T> {
+    fn insert(&self, val: u64) {
+        self.write().unwrap().put(&val);
+    }
 
-    for handle in handles {
-        handle.join().unwrap();
+    fn count(&self) -> f64 {
+        if Self::exclusive_count() {
+            self.write().unwrap().get_count()
+        } else {
+            self.read().unwrap().peek_count().unwrap()
+        }
     }
 
-    println!("\nFinal count: {}", count());
-    let time = now.elapsed();
-    println!("Time: {} ms", time.as_millis());
-    println!(
-        "Time per insert: {} ns",
-        time.as_nanos() as f64 / TOTAL_ITERATIONS as f64
+    fn name() -> String {
+        format!("RwLock<{}>", T::name())
+    }
+
+    fn exclusive_count() -> bool {
+        !T::capabilities().shared_count
+    }
+}
+
+impl SharedSketch for AtomicHyperLogLog<RandomState> {
+    fn insert(&self, val: u64) {
+        AtomicHyperLogLog::insert(self, &val);
+    }
+
+    fn count(&self) -> f64 {
+        AtomicHyperLogLog::count(self) as f64
+    }
+
+    fn name() -> String {
+        <Self as Container<u64>>::name().to_string()
+    }
+
+    fn exclusive_count() -> bool {
+        false
+    }
+}
+
+/// Settings of the concurrent benchmark.
+#[derive(Clone, Debug)]
+pub struct PerfConfig {
+    pub num_threads: usize,
+    /// Inserts across all threads, in each repetition.
+    pub num_inserts: usize,
+    /// Counts across all threads, in each repetition.
+    pub num_counts: usize,
+    /// Every thread's keys are derived from this seed and the thread index, so runs with the
+    /// same seed insert the same multiset.
+    pub seed: u64,
+    /// Unmeasured repetitions before the measured ones.
+    pub warmup: usize,
+    /// Measured repetitions, each on a new sketch. At least one, see [`PerfConfig::validate`].
+    pub repetitions: usize,
+    /// Which CPUs the threads are pinned to.
+    pub placement: topology::Placement,
+}
+
+impl Default for PerfConfig {
+    /// A random seed, printed with the results so the run can be repeated.
+    fn default() -> Self {
+        Self {
+            num_threads: 16,
+            num_inserts: TOTAL_ITERATIONS,
+            num_counts: TOTAL_ITERATIONS,
+            seed: rand::random(),
+            warmup: 1,
+            repetitions: 10,
+            placement: topology::Placement::Unpinned,
+        }
+    }
+}
+
+impl PerfConfig {
+    /// Rejects settings that would leave nothing to measure.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.num_threads == 0 {
+            return Err("num_threads must be at least 1".to_string());
+        }
+        if self.repetitions == 0 {
+            return Err("repetitions must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Runs the concurrent benchmark on every implementation. The sketches are built with fixed
+/// hasher seeds, so with the same `config.seed` the final counts are comparable across runs
+/// and library versions. Fails if `config` doesn't [`PerfConfig::validate`].
+pub fn perf(config: PerfConfig) -> Result<(), String> {
+    config.validate()?;
+    println!("Seed: {}", config.seed);
+    let cpus = config
+        .placement
+        .assign(&topology::cpus(), config.num_threads);
+    match &cpus {
+        Some(cpus) => println!(
+            "Placement: {}, threads on CPUs {:?}",
+            config.placement, cpus
+        ),
+        None => println!("Placement: {}", config.placement),
+    }
+    let config = &config;
+    let cpus = cpus.as_deref();
+    let hasher = || RandomState::with_seeds(0, 0, 0, 0);
+    perf_inner(
+        || RwLock::new(hyperloglogplus::HyperLogLogPF::<u64, _>::new(PRECISION, hasher()).unwrap()),
+        config,
+        cpus,
+    );
+    perf_inner(
+        || {
+            RwLock::new(
+                hyperloglogplus::HyperLogLogPlus::<u64, _>::new(PRECISION, hasher()).unwrap(),
+            )
+        },
+        config,
+        cpus,
+    );
+    perf_inner(
+        || {
+            RwLock::new(cardinality_estimator::CardinalityEstimator::<
+                u64,
+                ahash::AHasher,
+                10,
+                6,
+            >::new())
+        },
+        config,
+        cpus,
+    );
+    perf_inner(
+        || {
+            RwLock::new(
+                probabilistic_collections::hyperloglog::HyperLogLog::<u64, _>::with_hasher(
+                    container::prob_err(PRECISION),
+                    hasher(),
+                ),
+            )
+        },
+        config,
+        cpus,
+    );
+    // amadeus_streaming takes no hasher.
+    perf_inner(
+        || RwLock::new(<amadeus_streaming::HyperLogLog<u64> as Container<u64>>::init(PRECISION)),
+        config,
+        cpus,
+    );
+    perf_inner(
+        || RwLock::new(HyperLogLog::with_hasher(PRECISION, hasher())),
+        config,
+        cpus,
     );
+    perf_inner(
+        || AtomicHyperLogLog::with_hasher(PRECISION, hasher()),
+        config,
+        cpus,
+    );
+    Ok(())
+}
 
+/// SplitMix64 finalizer, a bijection on `u64` that turns a counter into well mixed keys. It
+/// costs a few multiplies, much less than `ThreadRng`, and is timed on its own so the insert
+/// times can exclude it.
+#[inline]
+pub fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Runs `f(t)` on threads `t` in `0..num_threads` at once, each pinned to `cpus[t]` if
+/// given, and returns the wall time.
+fn run_threads(num_threads: usize, cpus: Option<&[usize]>, f: impl Fn(usize) + Sync) -> Duration {
+    let f = &f;
     let now = Instant::now();
-    let handles: Vec<_> = (0..num_threads)
-        .map(|_| {
-            thread::spawn(move || {
-                for _ in 0..num_iterations_count {
-                    let _ = count();
+    thread::scope(|s| {
+        for t in 0..num_threads {
+            s.spawn(move || {
+                if let Some(cpus) = cpus {
+                    topology::pin(cpus[t]);
                 }
-            })
-        })
-        .collect();
+                f(t)
+            });
+        }
+    });
+    now.elapsed()
+}
 
-    for handle in handles {
-        handle.join().unwrap();
-    }
+/// Calls `f` with `num_iterations` keys on each of `num_threads` threads, and returns the wall
+/// time per call in ns. Thread `t` gets the keys of counters `t * num_iterations..` offset by
+/// the mixed `seed`, so the keys are distinct and the same for the same seed.
+fn time_per_key(
+    num_threads: usize,
+    num_iterations: usize,
+    cpus: Option<&[usize]>,
+    seed: u64,
+    f: impl Fn(u64) + Sync,
+) -> f64 {
+    let offset = splitmix64(seed);
+    let time = run_threads(num_threads, cpus, |t| {
+        let start = offset.wrapping_add((t * num_iterations) as u64);
+        for i in 0..num_iterations as u64 {
+            f(splitmix64(start.wrapping_add(i)));
+        }
+    });
+    time.as_nanos() as f64 / (num_threads * num_iterations) as f64
+}
+
+/// Times counting on `num_threads` threads, and returns the wall time per count in ns.
+fn time_per_count<S: SharedSketch>(
+    sketch: &S,
+    num_threads: usize,
+    num_iterations: usize,
+    cpus: Option<&[usize]>,
+) -> f64 {
+    let time = run_threads(num_threads, cpus, |_| {
+        for _ in 0..num_iterations {
+            std::hint::black_box(sketch.count());
+        }
+    });
+    time.as_nanos() as f64 / (num_threads * num_iterations) as f64
+}
+
+/// Runs `config.warmup` unmeasured and `config.repetitions` measured repetitions, each
+/// inserting into a new sketch from `new` and then counting it, and prints statistics of
+/// the time per insert and per count.
+fn perf_inner<S: SharedSketch>(new: impl Fn() -> S, config: &PerfConfig, cpus: Option<&[usize]>) {
+    let num_threads = config.num_threads;
+    let num_iterations = config.num_inserts / num_threads;
+    let num_iterations_count = config.num_counts / num_threads;
 
-    let time = now.elapsed();
-    println!("Time: {} ms", time.as_millis());
     println!(
-        "Time per count: {} ns",
-        time.as_nanos() as f64 / total_iterations_count as f64
+        "\n{} (count takes {} access)",
+        S::name(),
+        if S::exclusive_count() {
+            "exclusive"
+        } else {
+            "shared"
+        }
     );
+
+    let (mut keys, mut inserts, mut counts) = (Vec::new(), Vec::new(), Vec::new());
+    for rep in 0..config.warmup + config.repetitions {
+        let sketch = new();
+        // Generating the keys alone, the baseline subtracted from the insert time.
+        let key = time_per_key(num_threads, num_iterations, cpus, config.seed, |x| {
+            std::hint::black_box(x);
+        });
+        let insert = time_per_key(num_threads, num_iterations, cpus, config.seed, |x| {
+            sketch.insert(x)
+        });
+        let count = time_per_count(&sketch, num_threads, num_iterations_count, cpus);
+        if rep == config.warmup {
+            // Every repetition inserts the same keys, so this is the same each time.
+            println!("Final count: {}", sketch.count() as usize);
+        }
+        if rep >= config.warmup {
+            keys.push(key);
+            inserts.push(insert - key);
+            counts.push(count);
+        }
+    }
+
+    println!("Time per key generated (ns): {}", stats::Stats::new(&keys));
+    println!("Time per insert (ns): {}", stats::Stats::new(&inserts));
+    println!("Time per count (ns): {}", stats::Stats::new(&counts));
+}
+
+/// Runs `f` on its own thread and returns the panic message if it panics, so one failing
+/// implementation or precision doesn't abort a whole sweep.
+pub fn isolated<R: Send>(f: impl FnOnce() -> R + Send) -> Result<R, String> {
+    thread::scope(|s| s.spawn(f).join()).map_err(|payload| {
+        payload
+            .downcast_ref::<&str>()
+            .map(|msg| msg.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string())
+    })
+}
+
+/// Errors of one trial at one checkpoint.
+#[derive(Clone, Copy, Debug)]
+pub struct Checkpoint {
+    pub num_items: u64,
+    /// Relative error of [`Container::get_count`].
+    pub err: f64,
+    /// Relative error of [`Container::get_raw_count`], `NaN` if there is none.
+    pub raw_err: f64,
+    /// Inline size of the sketch plus the heap it holds, which is only tracked when
+    /// [`alloc::CountingAllocator`] is the global allocator.
+    pub bytes: usize,
+}
+
+/// Average, minimum and maximum of an error over the trials, all `NaN` if any error is.
+#[derive(Clone, Copy, Debug)]
+pub struct ErrStats {
+    pub avg: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl ErrStats {
+    pub(crate) fn new(errs: impl Iterator<Item = f64>) -> Self {
+        let mut total = 0.0f64;
+        let mut min = f64::MAX;
+        let mut max = f64::MIN;
+        let mut n = 0;
+        for err in errs {
+            total += err;
+            min = min.min(err);
+            max = max.max(err);
+            n += 1;
+        }
+        if total.is_nan() || n == 0 {
+            return Self {
+                avg: f64::NAN,
+                min: f64::NAN,
+                max: f64::NAN,
+            };
+        }
+        Self {
+            avg: total / n as f64,
+            min,
+            max,
+        }
+    }
+}
+
+/// Errors at one checkpoint over all trials, see [`accuarcy`].
+#[derive(Clone, Copy, Debug)]
+pub struct AccuarcyRow {
+    pub num_items: u64,
+    /// Error of the estimate the implementation reports, bias corrected where it offers that.
+    pub err: ErrStats,
+    /// Error of the raw estimate, where the implementation offers one.
+    pub raw_err: ErrStats,
+    /// Average memory footprint.
+    pub bytes: usize,
+}
+
+/// The CSV row `num_items,avg,min,max,bytes,raw_avg,raw_min,raw_max`. The raw columns are
+/// last so readers of the older five columns still work.
+impl std::fmt::Display for AccuarcyRow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{},{},{},{},{},{},{},{}",
+            self.num_items,
+            self.err.avg,
+            self.err.min,
+            self.err.max,
+            self.bytes,
+            self.raw_err.avg,
+            self.raw_err.min,
+            self.raw_err.max
+        )
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -142,11 +408,13 @@ pub enum Step {
     Pow2(u32),
 }
 
+/// Yields the errors of the corrected and raw estimates and the average memory footprint of the
+/// sketch at each checkpoint (see [`single_trial_accuarcy`]).
 pub fn accuarcy<T: Container<u64>>(
     max_size: u64,
     step: Step,
     precision: u8,
-) -> impl Iterator<Item = (u64, f64, f64, f64)> {
+) -> impl Iterator<Item = AccuarcyRow> {
     let num_trials: u64 = 8; //128;
     let data = (0..num_trials)
         .into_par_iter()
@@ -160,24 +428,98 @@ pub fn accuarcy<T: Container<u64>>(
         })
         .collect::<Vec<_>>();
 
-    let rows = min_len(&data);
-    (0..rows).map(move |i| {
-        let mut total = 0.0f64;
-        let mut min = f64::MAX;
-        let mut max = f64::MIN;
+    summarize(data)
+}
 
-        for j in 0..num_trials as usize {
-            let err = data[j][i].1;
-            total += err;
-            if err < min {
-                min = err;
-            }
-            if err > max {
-                max = err;
-            }
+/// Like [`accuarcy`], but simulates the register updates instead of hashing items (see
+/// [`sim`]), so `max_size` can go up to `u64::MAX`.
+pub fn simulated_accuarcy<T: Container<u64> + sim::RegisterSketch>(
+    max_size: u64,
+    step: Step,
+    precision: u8,
+) -> impl Iterator<Item = AccuarcyRow> {
+    let num_trials: u64 = 32;
+    let data = (0..num_trials)
+        .into_par_iter()
+        .map(|seed| sim::single_trial_simulation::<T>(max_size, step, precision, seed))
+        .collect::<Vec<_>>();
+    summarize(data)
+}
+
+/// Accuracy of every estimator in [`estimators::ESTIMATORS`] over the registers of the same
+/// sketches, so the estimates at each checkpoint come from identical register states. Returns
+/// the rows of [`accuarcy`] for each estimator, with `NaN` errors where it doesn't apply.
+pub fn estimator_accuarcy<T: Container<u64> + AsRef<[u8]>>(
+    max_size: u64,
+    step: Step,
+    precision: u8,
+) -> Vec<(&'static str, Vec<AccuarcyRow>)> {
+    let num_trials: u64 = 8;
+    let data = (0..num_trials)
+        .into_par_iter()
+        .map(|offset| {
+            single_trial_estimators::<T>(
+                max_size,
+                step,
+                precision,
+                offset.wrapping_mul(u64::MAX / num_trials),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    estimators::ESTIMATORS
+        .iter()
+        .enumerate()
+        .map(|(i, estimator)| {
+            let trials = data.iter().map(|trial| trial[i].clone()).collect();
+            (estimator.name, summarize(trials).collect())
+        })
+        .collect()
+}
+
+/// Returns the checkpoints of each estimator in [`estimators::ESTIMATORS`], from a single
+/// pass over the items. Each estimator only has one estimate, so `raw_err` is `NaN`.
+fn single_trial_estimators<T: Container<u64> + AsRef<[u8]>>(
+    max_size: u64,
+    step: Step,
+    precision: u8,
+    offset: u64,
+) -> Vec<Vec<Checkpoint>> {
+    let mut res = vec![Vec::new(); estimators::ESTIMATORS.len()];
+    let (mut hll, heap) = alloc::measure(|| T::init(precision));
+    let bytes = std::mem::size_of::<T>() + heap.max(0) as usize;
+    let mut x = 0;
+    for checkpoint in sim::checkpoints(max_size, step) {
+        while x < checkpoint {
+            x += 1;
+            hll.put(&(x + offset));
+        }
+        let real = x as f64;
+        for (rows, estimator) in res.iter_mut().zip(estimators::ESTIMATORS.iter()) {
+            let err = (estimator.estimate)(hll.as_ref())
+                .map_or(f64::NAN, |count| (count - real).abs() / real);
+            rows.push(Checkpoint {
+                num_items: x,
+                err,
+                raw_err: f64::NAN,
+                bytes,
+            });
         }
+    }
+    res
+}
 
-        (data[0][i].0, total / num_trials as f64, min, max)
+/// Summarizes the errors and bytes of each checkpoint over the trials in `data`.
+fn summarize(data: Vec<Vec<Checkpoint>>) -> impl Iterator<Item = AccuarcyRow> {
+    let rows = min_len(&data);
+    (0..rows).map(move |i| {
+        let trials = || data.iter().map(move |trial| trial[i]);
+        AccuarcyRow {
+            num_items: data[0][i].num_items,
+            err: ErrStats::new(trials().map(|c| c.err)),
+            raw_err: ErrStats::new(trials().map(|c| c.raw_err)),
+            bytes: trials().map(|c| c.bytes).sum::<usize>() / data.len(),
+        }
     })
 }
 
@@ -185,18 +527,33 @@ fn min_len<T>(vecs: &[Vec<T>]) -> usize {
     vecs.iter().min_by_key(|v| v.len()).unwrap().len()
 }
 
+/// Returns the errors and memory footprint of one trial at each checkpoint.
 pub fn single_trial_accuarcy<T: Container<u64>>(
     max_size: u64,
     step: Step,
     precision: u8,
     offset: u64,
-) -> Vec<(u64, f64)> {
+) -> Vec<Checkpoint> {
+    single_trial_accuarcy_with::<T>(max_size, step, SizeParam::Precision(precision), offset)
+}
+
+/// Like [`single_trial_accuarcy`], but sizes the sketch with its native size parameter.
+pub fn single_trial_accuarcy_with<T: Container<u64>>(
+    max_size: u64,
+    step: Step,
+    param: SizeParam,
+    offset: u64,
+) -> Vec<Checkpoint> {
     let res_size = match step {
         Step::Linear(s) => max_size.div_ceil(s) as usize,
         Step::Pow2(s) => ((1 << s) * max_size.ilog2()) as usize,
     };
     let mut res = Vec::with_capacity(res_size);
-    let mut hll = T::init(precision);
+    // The thread's heap is only read at the checkpoints, as the growth since the start less
+    // whatever `res` grew by.
+    let reserved = |res: &Vec<Checkpoint>| (res.capacity() * size_of::<Checkpoint>()) as isize;
+    let start = alloc::allocated() - reserved(&res);
+    let mut hll = T::init_with(param);
     for x in 1..=max_size {
         hll.put(&(x + offset));
 
@@ -215,9 +572,21 @@ pub fn single_trial_accuarcy<T: Container<u64>>(
         };
         if record {
             let real = x as f64;
-            let diff = (hll.get_count() - real).abs();
+            // Some sketches flush buffered state when counting, which may allocate.
+            let count = hll.get_count();
+            let diff = (count - real).abs();
             let err = diff / real;
-            res.push((x, err));
+            let raw_err = hll
+                .get_raw_count()
+                .map_or(f64::NAN, |raw| (raw - real).abs() / real);
+            let heap = alloc::allocated() - reserved(&res) - start;
+            let bytes = std::mem::size_of::<T>() + heap.max(0) as usize;
+            res.push(Checkpoint {
+                num_items: x,
+                err,
+                raw_err,
+                bytes,
+            });
 
             //if x.is_power_of_two() {
             //    print!("{},", x);
@@ -230,3 +599,47 @@ pub fn single_trial_accuarcy<T: Container<u64>>(
     }
     res
 }
+
+/// Measures the memory used and the error of a sketch sized by `param`, for comparing
+/// implementations by accuracy per byte.
+///
+/// Returns `(bytes, rmse)`: the average footprint after inserting `num_items` distinct items,
+/// and the root-mean-square relative error pooled over all trials and the checkpoints in the
+/// last octave before `num_items`. Pooling over an octave averages out the periodic bias of
+/// HLL-style estimators. `None` if there are no such checkpoints, e.g. if every trial stopped
+/// early.
+pub fn memory_efficiency<T: Container<u64>>(
+    param: SizeParam,
+    num_items: u64,
+    num_trials: u64,
+) -> Option<(usize, f64)> {
+    let data = (0..num_trials)
+        .into_par_iter()
+        .map(|offset| {
+            single_trial_accuarcy_with::<T>(
+                num_items,
+                Step::Pow2(4),
+                param,
+                offset.wrapping_mul(u64::MAX / num_trials),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let mut sum_sq = 0.0f64;
+    let mut num_errs = 0;
+    let mut bytes = 0;
+    for trial in data.iter() {
+        for c in trial.iter().filter(|c| c.num_items > num_items / 2) {
+            sum_sq += c.err * c.err;
+            num_errs += 1;
+        }
+        bytes += trial.last().map_or(0, |c| c.bytes);
+    }
+    if num_errs == 0 {
+        return None;
+    }
+    Some((
+        bytes / num_trials as usize,
+        (sum_sq / num_errs as f64).sqrt(),
+    ))
+}
@@ -1,21 +1,148 @@
+use bench_hll::alloc::CountingAllocator;
 use bench_hll::*;
+use std::fmt::Display;
 use std::fs::File;
 use std::io::prelude::*;
 use std::time::Instant;
 
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
 fn file_friendly_name(input: &str) -> String {
     input.replace("::", "__")
 }
 
-fn write_acc_data<T: Container<u64>>(precision: u8) -> std::io::Result<()> {
+/// Outcome of one job of a sweep.
+enum Status {
+    Ok,
+    /// The implementation doesn't support the parameter, so the job wasn't run.
+    Unsupported,
+    /// The job wasn't run for the given reason, e.g. it wouldn't fit in memory.
+    Skipped(String),
+    /// The job panicked or failed to write its results, with the message.
+    Failed(String),
+}
+
+/// Outcome of each job of a sweep, written to `<dir>/summary.csv` as
+/// `name,param,status,message`.
+#[derive(Default)]
+struct Summary(Vec<(String, String, Status)>);
+
+impl Summary {
+    /// Runs one implementation/parameter job on its own thread (see [`isolated`]) and records
+    /// whether it succeeded, so a panic only fails its own cell.
+    fn run<R: Send>(
+        &mut self,
+        name: &str,
+        param: impl Display,
+        job: impl FnOnce() -> std::io::Result<R> + Send,
+    ) -> Option<R> {
+        let res = isolated(job).and_then(|res| res.map_err(|e| e.to_string()));
+        if let Err(msg) = &res {
+            println!("{} {} failed: {}", name, param, msg);
+        }
+        let status = match &res {
+            Ok(_) => Status::Ok,
+            Err(msg) => Status::Failed(msg.clone()),
+        };
+        self.0.push((name.to_string(), param.to_string(), status));
+        res.ok()
+    }
+
+    /// Records a job that was skipped because the implementation doesn't support `param`.
+    fn unsupported(&mut self, name: &str, param: impl Display) {
+        println!("{} {} unsupported", name, param);
+        self.0
+            .push((name.to_string(), param.to_string(), Status::Unsupported));
+    }
+
+    /// Records a job that was skipped because `T` doesn't support `precision`, unless another
+    /// implementation with the same name does (see [`name_supports_precision`]), in which case
+    /// that one's job covers the cell.
+    fn unsupported_precision<T: Container<u64>>(&mut self, precision: u8, param: impl Display) {
+        if !name_supports_precision(T::name(), precision) {
+            self.unsupported(T::name(), param);
+        }
+    }
+
+    /// Records a job that was skipped for `reason`.
+    fn skipped(&mut self, name: &str, param: impl Display, reason: String) {
+        println!("{} {} skipped: {}", name, param, reason);
+        self.0
+            .push((name.to_string(), param.to_string(), Status::Skipped(reason)));
+    }
+
+    fn write(&self, dir: &str) -> std::io::Result<()> {
+        let mut file = File::create(format!("{}/summary.csv", dir))?;
+        for (name, param, status) in self.0.iter() {
+            let row = match status {
+                Status::Ok => format!("{},{},ok,\n", name, param),
+                Status::Unsupported => format!("{},{},unsupported,\n", name, param),
+                Status::Skipped(msg) => format!(
+                    "{},{},skipped,\"{}\"\n",
+                    name,
+                    param,
+                    msg.replace('"', "\"\"")
+                ),
+                Status::Failed(msg) => format!(
+                    "{},{},failed,\"{}\"\n",
+                    name,
+                    param,
+                    msg.replace('"', "\"\"")
+                ),
+            };
+            file.write_all(row.as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+/// Items of the accuracy sweep.
+const ACC_NUM_ITEMS: u64 = 4_000_000_000;
+/// Items of the accuracy sweep for the exact baselines, which hold every item in each of the
+/// parallel trials.
+const ACC_EXACT_NUM_ITEMS: u64 = 1 << 22;
+
+fn run_acc<T: Container<u64>>(summary: &mut Summary, precision: u8) {
+    if !T::supports_precision(precision) {
+        return summary.unsupported_precision::<T>(precision, precision);
+    }
+    let num_items = if T::capabilities().exact {
+        ACC_EXACT_NUM_ITEMS
+    } else {
+        ACC_NUM_ITEMS
+    };
+    summary.run(T::name(), precision, || {
+        write_acc_data::<T>(precision, num_items)
+    });
+}
+
+fn write_acc_data<T: Container<u64>>(precision: u8, num_items: u64) -> std::io::Result<()> {
     let now = Instant::now();
-    let res = accuarcy::<T>(4_000_000_000, Step::Pow2(6), precision);
+    let res = accuarcy::<T>(num_items, Step::Pow2(6), precision);
     let name = format!("Acc/{}.csv", file_friendly_name(T::name()));
     println!("name: {}", name);
     let mut file = File::create(name)?;
-    for (num_items, avg, min, max) in res {
-        let row = format!("{},{},{},{}\n", num_items, avg, min, max);
-        file.write_all(row.as_bytes())?;
+    for row in res {
+        writeln!(file, "{}", row)?;
+    }
+    println!(
+        "{} complete in {} seconds",
+        T::name(),
+        now.elapsed().as_secs()
+    );
+    Ok(())
+}
+
+/// Like [`write_acc_data`], but with simulated register updates, so it reaches `u64::MAX`.
+fn write_sim_data<T: Container<u64> + sim::RegisterSketch>(precision: u8) -> std::io::Result<()> {
+    let now = Instant::now();
+    let res = simulated_accuarcy::<T>(u64::MAX, Step::Pow2(6), precision);
+    let name = format!("Sim/{}.csv", file_friendly_name(T::name()));
+    println!("name: {}", name);
+    let mut file = File::create(name)?;
+    for row in res {
+        writeln!(file, "{}", row)?;
     }
     println!(
         "{} complete in {} seconds",
@@ -25,25 +152,383 @@ fn write_acc_data<T: Container<u64>>(precision: u8) -> std::io::Result<()> {
     Ok(())
 }
 
+/// Error curves up to 2^64 items for the implementations that expose their registers.
+fn sim() {
+    std::fs::create_dir_all("Sim").unwrap();
+    let mut summary = Summary::default();
+    type Apache = apache_hll::HyperLogLog<u64>;
+    summary.run(Apache::name(), 14, || write_sim_data::<Apache>(14));
+    summary.write("Sim").unwrap();
+}
+
+/// Compares the estimators in [`estimators`] on the registers of `T`, one file per estimator.
+fn write_est_data<T: Container<u64> + AsRef<[u8]>>(precision: u8) -> std::io::Result<()> {
+    let now = Instant::now();
+    for (estimator, res) in estimator_accuarcy::<T>(4_000_000_000, Step::Pow2(6), precision) {
+        let name = format!("Est/{}.csv", estimator);
+        println!("name: {}", name);
+        let mut file = File::create(name)?;
+        for row in res {
+            writeln!(file, "{}", row)?;
+        }
+    }
+    println!(
+        "{} estimators complete in {} seconds",
+        T::name(),
+        now.elapsed().as_secs()
+    );
+    Ok(())
+}
+
+fn est() {
+    std::fs::create_dir_all("Est").unwrap();
+    let mut summary = Summary::default();
+    type Apache = apache_hll::HyperLogLog<u64>;
+    summary.run(Apache::name(), 14, || write_est_data::<Apache>(14));
+    summary.write("Est").unwrap();
+}
+
+const MEM_NUM_ITEMS: u64 = 1 << 20;
+const MEM_NUM_TRIALS: u64 = 32;
+
+/// `(param, bytes, rmse)`, see [`memory_efficiency`].
+type MemRow = (SizeParam, usize, f64);
+
+fn mem_rows<T: Container<u64>>(summary: &mut Summary) -> Vec<MemRow> {
+    T::size_params()
+        .into_iter()
+        .filter_map(|param| {
+            if let SizeParam::Precision(p) = param
+                && !T::supports_precision(p)
+            {
+                summary.unsupported_precision::<T>(p, param);
+                return None;
+            }
+            let (bytes, rmse) = summary.run(T::name(), param, || {
+                memory_efficiency::<T>(param, MEM_NUM_ITEMS, MEM_NUM_TRIALS).ok_or_else(|| {
+                    std::io::Error::other("no checkpoints in the last octave before the items")
+                })
+            })?;
+            println!("{} {}: {} bytes, {} rmse", T::name(), param, bytes, rmse);
+            Some((param, bytes, rmse))
+        })
+        .collect()
+}
+
+fn write_mem_data(name: &str, rows: &[MemRow]) -> std::io::Result<()> {
+    let name = format!("Mem/{}.csv", file_friendly_name(name));
+    println!("name: {}", name);
+    let mut file = File::create(name)?;
+    for (param, bytes, rmse) in rows {
+        let row = format!("{},{},{}\n", param, bytes, rmse);
+        file.write_all(row.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Collects the memory-efficiency rows of every implementation, grouped by name since some
+/// implementations have one type per size.
+struct MemVisitor<'a> {
+    summary: &'a mut Summary,
+    rows: Vec<(&'static str, Vec<MemRow>)>,
+}
+
+impl ContainerVisitor for MemVisitor<'_> {
+    fn visit<T: Container<u64>>(&mut self) {
+        let rows = mem_rows::<T>(self.summary);
+        match self.rows.iter_mut().find(|(name, _)| *name == T::name()) {
+            Some((_, all)) => all.extend(rows),
+            None => self.rows.push((T::name(), rows)),
+        }
+    }
+}
+
+/// Error-versus-memory comparison: sweeps each implementation's native size parameter.
+fn mem() {
+    std::fs::create_dir_all("Mem").unwrap();
+    let mut summary = Summary::default();
+    let mut visitor = MemVisitor {
+        summary: &mut summary,
+        rows: Vec::new(),
+    };
+    visit_all(&mut visitor);
+    for (name, rows) in visitor.rows {
+        write_mem_data(name, &rows).unwrap();
+    }
+    summary.write("Mem").unwrap();
+}
+
+const GROUP_BY_ROWS: usize = 10_000_000;
+const GROUP_BY_ITEMS: u64 = 10_000_000;
+const GROUP_BY_EXPONENT: f64 = 1.0;
+const GROUP_BY_PRECISION: u8 = 14;
+/// Implementations whose empty sketches alone would need more than this are skipped.
+const GROUP_BY_MEMORY_BUDGET: usize = 16 << 30;
+
+/// Runs every implementation on one GROUP BY workload, collecting the rows by name.
+struct GroupByVisitor<'a> {
+    summary: &'a mut Summary,
+    workload: &'a group_by::Workload,
+    rows: &'a mut Vec<(&'static str, Vec<group_by::GroupByRow>)>,
+}
+
+impl ContainerVisitor for GroupByVisitor<'_> {
+    fn visit<T: Container<u64>>(&mut self) {
+        let param = format!("groups={}", self.workload.num_groups);
+        if !T::supports_precision(GROUP_BY_PRECISION) {
+            return self
+                .summary
+                .unsupported_precision::<T>(GROUP_BY_PRECISION, param);
+        }
+        let needed = group_by::empty_bytes::<T>(GROUP_BY_PRECISION) * self.workload.truth.len();
+        if needed > GROUP_BY_MEMORY_BUDGET {
+            let reason = format!("needs at least {} bytes", needed);
+            return self.summary.skipped(T::name(), param, reason);
+        }
+        let workload = self.workload;
+        let Some(row) = self.summary.run(T::name(), &param, || {
+            Ok(group_by::group_by::<T>(workload, GROUP_BY_PRECISION))
+        }) else {
+            return;
+        };
+        println!(
+            "{} {}: {} bytes, {} ns/row, {} rmse",
+            T::name(),
+            param,
+            row.bytes,
+            row.ns_per_row,
+            row.rmse
+        );
+        match self.rows.iter_mut().find(|(name, _)| *name == T::name()) {
+            Some((_, all)) => all.push(row),
+            None => self.rows.push((T::name(), vec![row])),
+        }
+    }
+}
+
+/// `COUNT(DISTINCT) ... GROUP BY` with 10^3 to 10^7 groups, see [`group_by`].
+fn groupby() {
+    std::fs::create_dir_all("GroupBy").unwrap();
+    let mut summary = Summary::default();
+    let mut rows = Vec::new();
+    for num_groups in [1_000, 10_000, 100_000, 1_000_000, 10_000_000] {
+        let workload = group_by::Workload::new(
+            num_groups,
+            GROUP_BY_ROWS,
+            GROUP_BY_ITEMS,
+            GROUP_BY_EXPONENT,
+            GROUP_BY_EXPONENT,
+            num_groups,
+        );
+        println!("{} groups, {} occur", num_groups, workload.truth.len());
+        visit_all(&mut GroupByVisitor {
+            summary: &mut summary,
+            workload: &workload,
+            rows: &mut rows,
+        });
+    }
+    for (name, rows) in rows {
+        let name = format!("GroupBy/{}.csv", file_friendly_name(name));
+        println!("name: {}", name);
+        let mut file = File::create(name).unwrap();
+        for row in rows {
+            writeln!(file, "{}", row).unwrap();
+        }
+    }
+    summary.write("GroupBy").unwrap();
+}
+
+const SPARSE_NUM_TRIALS: u64 = 5;
+
+/// Profiles the sparse to dense transition of every sparse-capable implementation, see
+/// [`sparse::transition`], collecting the rows by name.
+struct SparseVisitor<'a> {
+    summary: &'a mut Summary,
+    rows: Vec<(&'static str, Vec<String>)>,
+}
+
+impl ContainerVisitor for SparseVisitor<'_> {
+    fn visit<T: Container<u64>>(&mut self) {
+        if !T::capabilities().sparse {
+            return;
+        }
+        let mut rows = Vec::new();
+        for precision in 4..=18 {
+            if !T::supports_precision(precision) {
+                self.summary
+                    .unsupported_precision::<T>(precision, precision);
+                continue;
+            }
+            let Some(transitions) = self.summary.run(T::name(), precision, || {
+                // Well past the point where the sparse list outgrows the registers.
+                let max_items = 16 << precision;
+                Ok((0..SPARSE_NUM_TRIALS)
+                    .map(|trial| sparse::transition::<T>(precision, max_items, trial * max_items))
+                    .collect::<Vec<_>>())
+            }) else {
+                continue;
+            };
+            for (trial, transition) in transitions.into_iter().enumerate() {
+                match transition {
+                    Some(t) => rows.push(format!("{},{},{}", precision, trial, t)),
+                    None => println!("{} {}: no transition found", T::name(), precision),
+                }
+            }
+        }
+        match self.rows.iter_mut().find(|(name, _)| *name == T::name()) {
+            Some((_, all)) => all.extend(rows),
+            None => self.rows.push((T::name(), rows)),
+        }
+    }
+}
+
+/// Cardinality, latency and memory of the sparse to dense transition at every precision.
+fn sparse() {
+    std::fs::create_dir_all("Sparse").unwrap();
+    let mut summary = Summary::default();
+    let mut visitor = SparseVisitor {
+        summary: &mut summary,
+        rows: Vec::new(),
+    };
+    visit_all(&mut visitor);
+    for (name, rows) in visitor.rows {
+        let name = format!("Sparse/{}.csv", file_friendly_name(name));
+        println!("name: {}", name);
+        let mut file = File::create(name).unwrap();
+        for row in rows {
+            writeln!(file, "{}", row).unwrap();
+        }
+    }
+    summary.write("Sparse").unwrap();
+}
+
+/// Lists every implementation with its [`Capabilities`], one row per name.
+struct CapabilitiesVisitor(Vec<(&'static str, Capabilities)>);
+
+impl ContainerVisitor for CapabilitiesVisitor {
+    fn visit<T: Container<u64>>(&mut self) {
+        if !self.0.iter().any(|(name, _)| *name == T::name()) {
+            self.0.push((T::name(), T::capabilities()));
+        }
+    }
+}
+
+/// Writes `capabilities.csv`, which the plotting scripts read to find the implementations.
+fn write_capabilities() -> std::io::Result<()> {
+    let mut visitor = CapabilitiesVisitor(Vec::new());
+    visit_all(&mut visitor);
+    let mut file = File::create("capabilities.csv")?;
+    file.write_all(format!("name,{}\n", Capabilities::NAMES.join(",")).as_bytes())?;
+    for (name, capabilities) in visitor.0 {
+        let flags: Vec<_> = capabilities.flags().iter().map(|f| f.to_string()).collect();
+        file.write_all(format!("{},{}\n", name, flags.join(",")).as_bytes())?;
+    }
+    Ok(())
+}
+
 fn main() {
+    match std::env::args().nth(1).as_deref() {
+        Some("mem") => mem(),
+        Some("perf") => {
+            // `perf <seed> <placement>` repeats a previous run, placement is `unpinned`,
+            // `spread`, `pack` or a CPU list such as `0-3,8`.
+            let mut config = PerfConfig::default();
+            if let Some(seed) = std::env::args().nth(2) {
+                config.seed = seed.parse().expect("seed must be a u64");
+            }
+            if let Some(placement) = std::env::args().nth(3) {
+                config.placement = placement.parse().unwrap();
+            }
+            if let Err(msg) = perf(config) {
+                eprintln!("invalid perf config: {}", msg);
+                std::process::exit(1);
+            }
+        }
+        Some("sim") => sim(),
+        Some("est") => est(),
+        Some("groupby") => groupby(),
+        Some("sparse") => sparse(),
+        Some("caps") => write_capabilities().unwrap(),
+        Some("all") => acc(true),
+        _ => acc(false),
+    }
+}
+
+/// Implementations in the default accuracy sweep, each of which takes hours at
+/// [`ACC_NUM_ITEMS`]. `cargo run --release -- all` runs every implementation.
+const ACC_DEFAULT: [&str; 4] = [
+    "hyperloglockless::HyperLogLog",
+    "hyperloglockless::HyperLogLogPlus",
+    "hyperloglogplus::HyperLogLogPF",
+    "hyperloglogplus::HyperLogLogPlus",
+];
+
+/// Runs the accuracy sweep at `precision` for the implementations in [`ACC_DEFAULT`], or for
+/// every implementation if `all`.
+struct AccVisitor<'a> {
+    summary: &'a mut Summary,
+    precision: u8,
+    all: bool,
+}
+
+impl ContainerVisitor for AccVisitor<'_> {
+    fn visit<T: Container<u64>>(&mut self) {
+        if self.all || ACC_DEFAULT.contains(&T::name()) {
+            run_acc::<T>(self.summary, self.precision);
+        }
+    }
+}
+
+fn acc(all: bool) {
+    write_capabilities().unwrap();
+    std::fs::create_dir_all("Acc").unwrap();
+    let mut summary = Summary::default();
+
     //for p in 12..=16 {
-    //    write_acc_data::<hyperloglockless::HyperLogLog<ahash::RandomState>>(p).unwrap();
+    //    run_acc::<hyperloglockless::HyperLogLog<bench_hll::FixedState>>(&mut summary, p);
     //}
-    //perf();
+    //perf(PerfConfig::default()).unwrap();
 
     let p = 4;
-    write_acc_data::<hyperloglockless::HyperLogLog<ahash::RandomState>>(p).unwrap();
-    write_acc_data::<hyperloglockless::HyperLogLogPlus<ahash::RandomState>>(p).unwrap();
+    visit_all(&mut AccVisitor {
+        summary: &mut summary,
+        precision: p,
+        all,
+    });
 
-    write_acc_data::<hyperloglogplus::HyperLogLogPF<u64, ahash::RandomState>>(p).unwrap();
-    write_acc_data::<hyperloglogplus::HyperLogLogPlus<u64, ahash::RandomState>>(p).unwrap();
+    summary.write("Acc").unwrap();
+}
 
-    //write_acc_data::<cardinality_estimator::CardinalityEstimator<u64, ahash::AHasher, 14, 6>>(p).unwrap();
-    //write_acc_data::<amadeus_streaming::HyperLogLog<u64>>(p).unwrap();
-    //write_acc_data::<probabilistic_collections::hyperloglog::HyperLogLog::<u64, ahash::RandomState>>(p).unwrap();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    //write_acc_data::<crate::apache_hll::HyperLogLog::<u64>>(p).unwrap();
+    #[test]
+    fn test_isolated() {
+        assert_eq!(isolated(|| 1), Ok(1));
+        assert_eq!(
+            isolated(|| -> u8 { panic!("static") }),
+            Err("static".to_string())
+        );
+        let n = 2;
+        assert_eq!(
+            isolated(|| -> u8 { panic!("formatted {}", n) }),
+            Err("formatted 2".to_string())
+        );
+    }
 
-    // This crashes index out of bound
-    // write_acc_data::<hyperloglog::HyperLogLog>(16).unwrap();
+    #[test]
+    fn test_summary_run() {
+        let mut summary = Summary::default();
+        let failed: Option<u8> = summary.run("a", 1, || panic!("out of bounds"));
+        assert!(failed.is_none());
+        // Later jobs still run.
+        assert_eq!(summary.run("b", 2, || Ok(3)), Some(3));
+        let failed: Option<u8> = summary.run("c", 3, || Err(std::io::Error::other("no file")));
+        assert!(failed.is_none());
+        assert!(matches!(&summary.0[0], (name, param, Status::Failed(msg))
+            if name == "a" && param == "1" && msg == "out of bounds"));
+        assert!(matches!(&summary.0[1], (name, param, Status::Ok) if name == "b" && param == "2"));
+        assert!(matches!(&summary.0[2], (_, _, Status::Failed(msg)) if msg == "no file"));
+    }
 }
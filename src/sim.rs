@@ -0,0 +1,216 @@
+//! Hash-free simulation of HLL register states.
+//!
+//! Inserting `n` distinct items into an HLL with `m` registers is equivalent to drawing `n`
+//! independent `(index, rank)` pairs, with the index uniform in `0..m` and the rank `r` taken
+//! with probability `2^-r` (the largest rank takes the remaining mass). Almost all of those
+//! draws leave the registers unchanged once `n` is large: with `count[v]` registers holding
+//! `v`, the next draw raises some register with probability
+//!
+//! ```text
+//! q = sum_v count[v] * 2^-v / m
+//! ```
+//!
+//! so the number of draws until the next change is geometric with parameter `q`. The simulation
+//! jumps straight from one register change to the next, which takes `O(m * log(n))` steps
+//! instead of `n` hashes, and reaches 2^64 items in seconds.
+
+use crate::{Checkpoint, Container, Step};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Register-level access to an HLL sketch, for sketches whose registers are updated with
+/// `register = max(register, rank)`.
+pub trait RegisterSketch {
+    /// Number of hash bits used for the register index, the sketch has `2^index_bits` registers.
+    fn index_bits(&self) -> u32;
+
+    /// Largest rank a register can hold. Lower ranks `r` occur with probability `2^-r`.
+    fn max_rank(&self) -> u8;
+
+    /// Updates register `index` as inserting a hash with that index and `rank` would.
+    fn update_register(&mut self, index: usize, rank: u8);
+}
+
+/// Registers grouped by value, so that a register with a given value can be picked uniformly.
+struct Groups {
+    /// Register indexes for each value.
+    members: Vec<Vec<u32>>,
+    /// Position of each register in its group.
+    slot: Vec<u32>,
+    /// Current value of each register.
+    value: Vec<u8>,
+}
+
+impl Groups {
+    fn new(num_registers: usize, max_rank: u8) -> Self {
+        let mut members = vec![Vec::new(); max_rank as usize + 1];
+        members[0] = (0..num_registers as u32).collect();
+        Self {
+            members,
+            slot: (0..num_registers as u32).collect(),
+            value: vec![0; num_registers],
+        }
+    }
+
+    fn set(&mut self, index: u32, new: u8) {
+        let old = self.value[index as usize] as usize;
+        let slot = self.slot[index as usize] as usize;
+        self.members[old].swap_remove(slot);
+        if let Some(&moved) = self.members[old].get(slot) {
+            self.slot[moved as usize] = slot as u32;
+        }
+        self.slot[index as usize] = self.members[new as usize].len() as u32;
+        self.members[new as usize].push(index);
+        self.value[index as usize] = new;
+    }
+
+    /// Weight of each value in the probability of the next draw changing a register.
+    fn weight(&self, value: usize) -> f64 {
+        self.members[value].len() as f64 * 2.0f64.powi(-(value as i32))
+    }
+}
+
+/// Checkpoints at which [`crate::single_trial_accuarcy`] records the error, up to `max_size`.
+pub fn checkpoints(max_size: u64, step: Step) -> Vec<u64> {
+    match step {
+        Step::Linear(s) => (1..=max_size / s).map(|i| i * s).collect(),
+        Step::Pow2(s) => (s + 1..64)
+            .flat_map(|ilog| (1u64 << s..2 << s).map(move |j| j << (ilog - s)))
+            .take_while(|&x| x <= max_size)
+            .collect(),
+    }
+}
+
+/// Number of draws up to and including the next register change.
+fn draws_to_change(rng: &mut StdRng, q: f64) -> u128 {
+    let u = 1.0 - rng.r#gen::<f64>();
+    // `as` saturates, so registers that can no longer change give u128::MAX.
+    ((u.ln() / (-q).ln_1p()).floor() as u128).saturating_add(1)
+}
+
+/// Like [`crate::single_trial_accuarcy`], but instead of hashing items, simulates the register
+/// updates through [`RegisterSketch`]. Trials with different `seed`s are independent.
+pub fn single_trial_simulation<T: Container<u64> + RegisterSketch>(
+    max_size: u64,
+    step: Step,
+    precision: u8,
+    seed: u64,
+) -> Vec<Checkpoint> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let (mut hll, heap) = crate::alloc::measure(|| T::init(precision));
+    let bytes = std::mem::size_of::<T>() + heap.max(0) as usize;
+    let num_registers = 1usize << hll.index_bits();
+    let max_rank = hll.max_rank();
+    let mut groups = Groups::new(num_registers, max_rank);
+
+    let mut res = Vec::new();
+    let change_probability = |groups: &Groups| {
+        (0..max_rank as usize)
+            .map(|v| groups.weight(v))
+            .sum::<f64>()
+            / num_registers as f64
+    };
+    let mut next_change = draws_to_change(&mut rng, change_probability(&groups));
+    for x in checkpoints(max_size, step) {
+        while next_change <= x as u128 {
+            // Pick the value of the changed register in proportion to its weight, then a
+            // register with that value uniformly.
+            let mut target =
+                rng.r#gen::<f64>() * change_probability(&groups) * num_registers as f64;
+            let mut value = 0;
+            while value + 1 < max_rank as usize && target >= groups.weight(value) {
+                target -= groups.weight(value);
+                value += 1;
+            }
+            while groups.members[value].is_empty() {
+                value -= 1;
+            }
+            let group = &groups.members[value];
+            let index = group[rng.gen_range(0..group.len())];
+
+            // Given that the rank exceeds `value`, it is `value + j` with probability `2^-j`.
+            let j = rng.r#gen::<u64>().trailing_zeros() + 1;
+            let rank = (value as u32 + j).min(max_rank as u32) as u8;
+            hll.update_register(index as usize, rank);
+            groups.set(index, rank);
+
+            next_change =
+                next_change.saturating_add(draws_to_change(&mut rng, change_probability(&groups)));
+        }
+
+        let real = x as f64;
+        let err = (hll.get_count() - real).abs() / real;
+        let raw_err = hll
+            .get_raw_count()
+            .map_or(f64::NAN, |raw| (raw - real).abs() / real);
+        res.push(Checkpoint {
+            num_items: x,
+            err,
+            raw_err,
+            bytes,
+        });
+        if err > 1000.0 {
+            break;
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apache_hll::HyperLogLog;
+
+    #[test]
+    fn test_checkpoints_match_accuracy() {
+        for step in [Step::Linear(7), Step::Pow2(2), Step::Pow2(6)] {
+            let expected: Vec<u64> =
+                crate::single_trial_accuarcy::<crate::exact::SortedVec>(5_000, step, 0, 0)
+                    .into_iter()
+                    .map(|c| c.num_items)
+                    .collect();
+            assert_eq!(checkpoints(5_000, step), expected);
+        }
+        assert_eq!(checkpoints(u64::MAX, Step::Pow2(0)).len(), 63);
+    }
+
+    #[test]
+    fn test_draws_to_change_saturates() {
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(draws_to_change(&mut rng, 0.0), u128::MAX);
+        assert_eq!(draws_to_change(&mut rng, 1.0), 1);
+    }
+
+    #[test]
+    fn test_groups() {
+        let mut groups = Groups::new(8, 5);
+        groups.set(3, 2);
+        groups.set(5, 2);
+        groups.set(3, 4);
+        groups.set(0, 5);
+        assert_eq!(groups.members[0].len(), 5);
+        assert_eq!(groups.members[2], vec![5]);
+        assert_eq!(groups.members[4], vec![3]);
+        for (v, members) in groups.members.iter().enumerate() {
+            for &i in members {
+                assert_eq!(groups.value[i as usize] as usize, v);
+                assert_eq!(groups.members[v][groups.slot[i as usize] as usize], i);
+            }
+        }
+    }
+
+    #[test]
+    fn test_simulated_error() {
+        // apache_hll has 2^14 registers, 1.04 / 128 ~ 0.8% standard error.
+        let trials: Vec<_> = (0..8)
+            .map(|seed| {
+                single_trial_simulation::<HyperLogLog<u64>>(1 << 40, Step::Pow2(0), 14, seed)
+            })
+            .collect();
+        for i in 0..trials[0].len() {
+            let rmse = (trials.iter().map(|t| t[i].err * t[i].err).sum::<f64>() / 8.0).sqrt();
+            assert!(rmse < 0.03, "{} at {}", rmse, trials[0][i].num_items);
+        }
+        assert_eq!(trials[0].last().unwrap().num_items, 1 << 40);
+    }
+}
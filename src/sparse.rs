@@ -0,0 +1,150 @@
+//! Profiling of the sparse to dense transition.
+//!
+//! Sketches with a sparse mode start with a small list of hashes and convert to registers once
+//! the list would outgrow them. The conversion happens inside one insert, which is much slower
+//! than the others, and changes the memory footprint at once.
+
+use crate::Container;
+use std::fmt;
+use std::time::Instant;
+
+/// How the insert that converts to dense was found.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Detection {
+    /// [`Container::is_sparse`] turned false.
+    IsSparse,
+    /// The last insert that changed the heap footprint, since a dense sketch doesn't allocate.
+    /// Needs the counting allocator (see [`crate::alloc`]).
+    Heap,
+}
+
+impl fmt::Display for Detection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Detection::IsSparse => write!(f, "is_sparse"),
+            Detection::Heap => write!(f, "heap"),
+        }
+    }
+}
+
+/// The insert at which a sketch converted to dense.
+#[derive(Clone, Copy, Debug)]
+pub struct Transition {
+    /// Number of distinct items, including the one that triggered the conversion.
+    pub num_items: u64,
+    /// Latency of the insert that triggered the conversion.
+    pub latency_ns: u128,
+    /// Median latency of the other inserts before it, for comparison.
+    pub median_latency_ns: u128,
+    /// Heap bytes before and after that insert.
+    pub bytes_before: isize,
+    pub bytes_after: isize,
+    pub detection: Detection,
+}
+
+impl fmt::Display for Transition {
+    /// `num_items,latency_ns,median_latency_ns,bytes_before,bytes_after,detection`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{},{},{},{},{},{}",
+            self.num_items,
+            self.latency_ns,
+            self.median_latency_ns,
+            self.bytes_before,
+            self.bytes_after,
+            self.detection
+        )
+    }
+}
+
+/// Inserts up to `max_items` distinct items, starting at `offset`, into a new sketch and
+/// returns the insert that converted it to dense. `None` if it didn't convert, or, for
+/// sketches that don't expose [`Container::is_sparse`], if the heap footprint changed too
+/// late to tell the conversion from growth of the sparse list.
+pub fn transition<T: Container<u64>>(
+    precision: u8,
+    max_items: u64,
+    offset: u64,
+) -> Option<Transition> {
+    let (mut hll, heap) = crate::alloc::measure(|| T::init(precision));
+    let detection = match hll.is_sparse() {
+        Some(true) => Detection::IsSparse,
+        // Already dense, e.g. a sparse mode that is only used for small precisions.
+        Some(false) => return None,
+        None => Detection::Heap,
+    };
+
+    let mut bytes = vec![heap];
+    let mut latencies = Vec::new();
+    for x in offset..offset + max_items {
+        let now = Instant::now();
+        let ((), delta) = crate::alloc::measure(|| hll.put(&x));
+        latencies.push(now.elapsed().as_nanos());
+        bytes.push(bytes.last().unwrap() + delta);
+
+        if detection == Detection::IsSparse && hll.is_sparse() == Some(false) {
+            break;
+        }
+    }
+
+    // Index of the converting insert, in `latencies` and `bytes[1..]`.
+    let i = match detection {
+        Detection::IsSparse if hll.is_sparse() == Some(false) => latencies.len() - 1,
+        Detection::IsSparse => return None,
+        Detection::Heap => {
+            let i = bytes.windows(2).rposition(|w| w[0] != w[1])?;
+            if i >= latencies.len() / 2 {
+                return None;
+            }
+            i
+        }
+    };
+    let mut before = latencies[..i].to_vec();
+    before.sort_unstable();
+    Some(Transition {
+        num_items: i as u64 + 1,
+        latency_ns: latencies[i],
+        median_latency_ns: before.get(before.len() / 2).copied().unwrap_or(0),
+        bytes_before: bytes[i],
+        bytes_after: bytes[i + 1],
+        detection,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FixedState;
+    use crate::hllpp::HyperLogLogPlus;
+
+    #[test]
+    fn test_transition() {
+        for precision in [4, 10, 14] {
+            let t =
+                transition::<HyperLogLogPlus<FixedState>>(precision, 16 << precision, 0).unwrap();
+            assert_eq!(t.detection, Detection::IsSparse);
+            // Converts once the sparse list would be as large as the registers.
+            let limit = (1u64 << precision) / 4;
+            assert!(
+                t.num_items > limit / 2 && t.num_items <= 4 * limit,
+                "{:?}",
+                t
+            );
+
+            let mut hll: HyperLogLogPlus<FixedState> = Container::init(precision);
+            for x in 0..t.num_items - 1 {
+                hll.put(&x);
+            }
+            assert!(HyperLogLogPlus::is_sparse(&hll));
+        }
+        // Never converts within the limit.
+        assert!(transition::<HyperLogLogPlus<FixedState>>(14, 10, 0).is_none());
+    }
+
+    #[test]
+    fn test_no_sparse_mode() {
+        // Dense from the start, and no allocator installed, so nothing to detect.
+        assert!(transition::<crate::apache_hll::HyperLogLog<u64>>(14, 1000, 0).is_none());
+    }
+}
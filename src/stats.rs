@@ -0,0 +1,100 @@
+//! Summary statistics of repeated measurements, for the multi-threaded benchmark that
+//! Criterion doesn't cover.
+
+use std::fmt;
+
+/// Mean, median, standard deviation and 95% confidence interval of the mean of a sample.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stats {
+    pub n: usize,
+    pub mean: f64,
+    pub median: f64,
+    /// Sample standard deviation, `NaN` for a single sample.
+    pub std_dev: f64,
+    /// Half width of the 95% confidence interval of the mean, from Student's t distribution.
+    pub ci95: f64,
+}
+
+impl Stats {
+    pub fn new(samples: &[f64]) -> Self {
+        let n = samples.len();
+        assert!(n > 0, "no samples");
+        let mean = samples.iter().sum::<f64>() / n as f64;
+
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let median = if n % 2 == 1 {
+            sorted[n / 2]
+        } else {
+            (sorted[n / 2 - 1] + sorted[n / 2]) / 2.0
+        };
+
+        let var = samples.iter().map(|x| (x - mean) * (x - mean)).sum::<f64>() / (n as f64 - 1.0);
+        let std_dev = var.sqrt();
+        let ci95 = t_975(n.saturating_sub(1)) * std_dev / (n as f64).sqrt();
+        Self {
+            n,
+            mean,
+            median,
+            std_dev,
+            ci95,
+        }
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.3} ± {:.3} (median {:.3}, std dev {:.3}, n = {})",
+            self.mean, self.ci95, self.median, self.std_dev, self.n
+        )
+    }
+}
+
+/// 97.5% quantile of Student's t distribution with `df` degrees of freedom.
+fn t_975(df: usize) -> f64 {
+    const TABLE: [f64; 30] = [
+        12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+        2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+        2.052, 2.048, 2.045, 2.042,
+    ];
+    match df {
+        0 => f64::NAN,
+        1..=30 => TABLE[df - 1],
+        // Cornish-Fisher expansion around the normal quantile, to second order in 1 / df.
+        _ => {
+            let z: f64 = 1.959964;
+            let df = df as f64;
+            z + (z.powi(3) + z) / (4.0 * df)
+                + (5.0 * z.powi(5) + 16.0 * z.powi(3) + 3.0 * z) / (96.0 * df * df)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        let stats = Stats::new(&[4.0, 1.0, 3.0, 2.0]);
+        assert_eq!(stats.n, 4);
+        assert_eq!(stats.mean, 2.5);
+        assert_eq!(stats.median, 2.5);
+        assert!((stats.std_dev - (5.0f64 / 3.0).sqrt()).abs() < 1e-12);
+        assert!((stats.ci95 - 3.182 * stats.std_dev / 2.0).abs() < 1e-12);
+
+        let single = Stats::new(&[7.0]);
+        assert_eq!(single.median, 7.0);
+        assert!(single.std_dev.is_nan() && single.ci95.is_nan());
+    }
+
+    #[test]
+    fn test_t_975() {
+        // Continuous at the end of the table and tends to the normal quantile.
+        assert!((t_975(31) - 2.040).abs() < 0.001);
+        assert!((t_975(120) - 1.980).abs() < 0.001);
+        assert!((t_975(1_000_000) - 1.96).abs() < 0.001);
+    }
+}
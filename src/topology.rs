@@ -0,0 +1,226 @@
+//! CPU topology from `/sys/devices/system/cpu`, and thread placement for the concurrent
+//! benchmark. Contention on shared registers depends on whether threads share a core (and its
+//! caches) or not, so the benchmark can spread threads over physical cores, pack them onto SMT
+//! siblings, or pin them to given CPUs.
+
+use std::fmt;
+use std::fs;
+use std::str::FromStr;
+
+const SYS_CPU: &str = "/sys/devices/system/cpu";
+
+/// A logical CPU.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cpu {
+    pub id: usize,
+    /// Physical core, SMT siblings share it. Only unique within a package.
+    pub core: usize,
+    pub package: usize,
+}
+
+/// Parses a kernel CPU list, e.g. `0-3,8,10-11`. An empty list or a reversed range such as
+/// `3-1` is an error.
+pub fn parse_list(s: &str) -> Result<Vec<usize>, String> {
+    let invalid = || format!("invalid CPU list: {:?}", s);
+    let mut res = Vec::new();
+    for part in s.trim().split(',').filter(|p| !p.is_empty()) {
+        let parse = |x: &str| x.trim().parse::<usize>().map_err(|_| invalid());
+        match part.split_once('-') {
+            Some((lo, hi)) => {
+                let (lo, hi) = (parse(lo)?, parse(hi)?);
+                if lo > hi {
+                    return Err(invalid());
+                }
+                res.extend(lo..=hi)
+            }
+            None => res.push(parse(part)?),
+        }
+    }
+    if res.is_empty() {
+        return Err(invalid());
+    }
+    Ok(res)
+}
+
+fn read_sys(path: &str) -> Option<String> {
+    fs::read_to_string(format!("{}/{}", SYS_CPU, path)).ok()
+}
+
+/// The online CPUs this process may run on, i.e. in its affinity mask, which also reflects a
+/// cgroup cpuset. Without `/sys` (not Linux), every CPU `core_affinity` reports is treated as
+/// its own core.
+pub fn cpus() -> Vec<Cpu> {
+    let allowed: Option<Vec<usize>> =
+        core_affinity::get_core_ids().map(|ids| ids.into_iter().map(|c| c.id).collect());
+    let from_sys = || -> Option<Vec<Cpu>> {
+        parse_list(&read_sys("online")?)
+            .ok()?
+            .into_iter()
+            .filter(|id| allowed.as_ref().is_none_or(|allowed| allowed.contains(id)))
+            .map(|id| {
+                let topology = |name| {
+                    read_sys(&format!("cpu{}/topology/{}", id, name))?
+                        .trim()
+                        .parse()
+                        .ok()
+                };
+                Some(Cpu {
+                    id,
+                    core: topology("core_id")?,
+                    package: topology("physical_package_id")?,
+                })
+            })
+            .collect()
+    };
+    from_sys().unwrap_or_else(|| {
+        core_affinity::get_core_ids()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|c| Cpu {
+                id: c.id,
+                core: c.id,
+                package: 0,
+            })
+            .collect()
+    })
+}
+
+/// Where the threads of the concurrent benchmark run.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Placement {
+    /// Not pinned, the scheduler places the threads.
+    #[default]
+    Unpinned,
+    /// Thread `i` is pinned to the `i`-th CPU of the list, wrapping around.
+    Cpus(Vec<usize>),
+    /// One thread per physical core, alternating packages, before any SMT sibling is used.
+    Spread,
+    /// All SMT siblings of a core are used before the next core, and all cores of a package
+    /// before the next package.
+    Pack,
+}
+
+impl Placement {
+    /// The CPU each of `num_threads` threads is pinned to, `None` if unpinned.
+    pub fn assign(&self, cpus: &[Cpu], num_threads: usize) -> Option<Vec<usize>> {
+        let order: Vec<usize> = match self {
+            Placement::Unpinned => return None,
+            Placement::Cpus(ids) => ids.clone(),
+            Placement::Pack => {
+                let mut sorted = cpus.to_vec();
+                sorted.sort_by_key(|c| (c.package, c.core, c.id));
+                sorted.iter().map(|c| c.id).collect()
+            }
+            Placement::Spread => {
+                // Rank each CPU among its SMT siblings and each core within its package, then
+                // take the first sibling of every core, interleaving packages, and so on.
+                let mut sorted = cpus.to_vec();
+                sorted.sort_by_key(|c| (c.package, c.core, c.id));
+                let mut keyed = Vec::new();
+                let (mut sibling, mut core_rank) = (0, 0);
+                for (i, c) in sorted.iter().enumerate() {
+                    if i > 0 {
+                        let prev = sorted[i - 1];
+                        if prev.package != c.package {
+                            (sibling, core_rank) = (0, 0);
+                        } else if prev.core != c.core {
+                            (sibling, core_rank) = (0, core_rank + 1);
+                        } else {
+                            sibling += 1;
+                        }
+                    }
+                    keyed.push(((sibling, core_rank, c.package), c.id));
+                }
+                keyed.sort();
+                keyed.into_iter().map(|(_, id)| id).collect()
+            }
+        };
+        assert!(!order.is_empty(), "no CPUs to place threads on");
+        Some((0..num_threads).map(|i| order[i % order.len()]).collect())
+    }
+}
+
+impl FromStr for Placement {
+    type Err = String;
+
+    /// `unpinned`, `spread`, `pack`, or a CPU list such as `0-3,8`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unpinned" => Ok(Placement::Unpinned),
+            "spread" => Ok(Placement::Spread),
+            "pack" => Ok(Placement::Pack),
+            _ => parse_list(s).map(Placement::Cpus),
+        }
+    }
+}
+
+impl fmt::Display for Placement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Placement::Unpinned => write!(f, "unpinned"),
+            Placement::Cpus(ids) => write!(f, "cpus {:?}", ids),
+            Placement::Spread => write!(f, "spread"),
+            Placement::Pack => write!(f, "pack"),
+        }
+    }
+}
+
+/// Pins the current thread to `cpu`.
+pub fn pin(cpu: usize) {
+    assert!(
+        core_affinity::set_for_current(core_affinity::CoreId { id: cpu }),
+        "could not pin thread to CPU {}",
+        cpu
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_list() {
+        assert_eq!(
+            parse_list("0-3,8,10-11\n").unwrap(),
+            vec![0, 1, 2, 3, 8, 10, 11]
+        );
+        assert_eq!(parse_list("5").unwrap(), vec![5]);
+        assert!(parse_list("0-x").is_err());
+        assert!(parse_list("3-1").is_err());
+        assert!(parse_list("").is_err());
+        assert!(parse_list(",").is_err());
+    }
+
+    #[test]
+    fn test_assign() {
+        // 2 packages with 2 cores of 2 SMT siblings each, siblings numbered as on x86 Linux.
+        let cpus: Vec<Cpu> = (0..8)
+            .map(|id| Cpu {
+                id,
+                core: id % 2,
+                package: (id / 2) % 2,
+            })
+            .collect();
+        assert_eq!(Placement::Unpinned.assign(&cpus, 4), None);
+        assert_eq!(
+            Placement::Pack.assign(&cpus, 8).unwrap(),
+            vec![0, 4, 1, 5, 2, 6, 3, 7]
+        );
+        assert_eq!(
+            Placement::Spread.assign(&cpus, 8).unwrap(),
+            vec![0, 2, 1, 3, 4, 6, 5, 7]
+        );
+        assert_eq!(
+            Placement::Cpus(vec![3, 5]).assign(&cpus, 3).unwrap(),
+            vec![3, 5, 3]
+        );
+    }
+
+    #[test]
+    fn test_parse_placement() {
+        assert_eq!("spread".parse(), Ok(Placement::Spread));
+        assert_eq!("1-2".parse(), Ok(Placement::Cpus(vec![1, 2])));
+        assert!("packed".parse::<Placement>().is_err());
+        assert!("".parse::<Placement>().is_err());
+    }
+}
@@ -0,0 +1,325 @@
+//! # UltraLogLog
+//!
+//! An implementation of "UltraLogLog: A Practical and More Space-Efficient Alternative to
+//! HyperLogLog for Approximate Distinct Counting", Otmar Ertl (VLDB 2024), for comparing the
+//! HLL crates against the state of the art at equal memory.
+//!
+//! Like HLL with one byte per register, an element updates register `i` (the top `p` bits of
+//! its 64-bit hash) with the update value `k` (the position of the first 1 bit after them).
+//! Unlike HLL, the byte also records whether `k - 1` and `k - 2` were seen:
+//!
+//! ```text
+//! register = (u << 2) | (seen(u - 1) << 1) | seen(u - 2),  u = max update value, 0 if empty
+//! ```
+//!
+//! Two estimators are provided:
+//! - [`UltraLogLog::count`], the further generalized remaining area (FGRA) estimator, which
+//!   falls back to maximum likelihood in the small range.
+//! - [`UltraLogLog::count_ml`], the maximum-likelihood estimator.
+
+use std::hash::{BuildHasher, Hash};
+
+pub const MIN_PRECISION: u8 = 4;
+pub const MAX_PRECISION: u8 = 26;
+
+/// Exponent of the FGRA estimator, tau in the paper.
+const TAU: f64 = 0.8194911375910897;
+
+/// FGRA register contributions for each combination of history bits, `eta` in the paper.
+/// Scaled so that the expected sum of contributions is `m * (n / m)^-TAU`.
+const ETA: [f64; 4] = [
+    4.663135422063788,
+    2.1378502137958524,
+    2.781144650979996,
+    0.9824082545153715,
+];
+
+/// Asymptotic relative variance of one register's contribution, divided by `TAU^2`.
+const FGRA_VARIANCE: f64 = 0.6118931496978437;
+
+/// Below this many items per register, registers with no history yet bias the FGRA estimate,
+/// so [`UltraLogLog::count`] uses the maximum-likelihood estimate instead.
+const FGRA_MIN_ITEMS_PER_REGISTER: f64 = 64.0;
+
+#[derive(Clone, Debug)]
+pub struct UltraLogLog<S = ahash::RandomState> {
+    registers: Vec<u8>,
+    precision: u8,
+    hasher: S,
+}
+
+impl<S: BuildHasher + Default> UltraLogLog<S> {
+    /// Creates a new, empty UltraLogLog with `2^precision` registers.
+    pub fn new(precision: u8) -> Self {
+        Self::with_hasher(precision, S::default())
+    }
+}
+
+impl<S: BuildHasher> UltraLogLog<S> {
+    /// Creates a new, empty UltraLogLog with `2^precision` registers.
+    ///
+    /// # Panics
+    /// If `precision` is outside of `4..=26`.
+    pub fn with_hasher(precision: u8, hasher: S) -> Self {
+        assert!(
+            (MIN_PRECISION..=MAX_PRECISION).contains(&precision),
+            "precision must be in {}..={}, got {}",
+            MIN_PRECISION,
+            MAX_PRECISION,
+            precision
+        );
+        Self {
+            registers: vec![0; 1 << precision],
+            precision,
+            hasher,
+        }
+    }
+
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+
+    /// Adds an element to the UltraLogLog.
+    #[inline]
+    pub fn insert<T: Hash + ?Sized>(&mut self, obj: &T) {
+        let hash = self.hasher.hash_one(obj);
+        self.insert_hash(hash);
+    }
+
+    /// Adds an already hashed element to the UltraLogLog.
+    #[inline]
+    pub fn insert_hash(&mut self, hash: u64) {
+        let p = self.precision;
+        let index = (hash >> (64 - p)) as usize;
+        let k = ((hash << p) | (1 << (p - 1))).leading_zeros() + 1;
+        let register = &mut self.registers[index];
+        *register = pack(unpack(*register) | (1 << k));
+    }
+
+    /// Merge the other [`UltraLogLog`] into this one.
+    ///
+    /// # Panics
+    /// If the precisions differ.
+    pub fn merge<T: BuildHasher>(&mut self, other: &UltraLogLog<T>) {
+        assert_eq!(
+            self.precision, other.precision,
+            "unexpected got unequal precisions"
+        );
+        for (r, o) in self.registers.iter_mut().zip(&other.registers) {
+            *r = pack(unpack(*r) | unpack(*o));
+        }
+    }
+
+    /// Guess the number of unique elements with the FGRA estimator.
+    pub fn count(&self) -> f64 {
+        let m = self.registers.len() as f64;
+        let mut sum = 0.0;
+        for &r in self.registers.iter() {
+            let u = (r >> 2) as i32;
+            sum += ETA[(r & 3) as usize] * 2.0f64.powf(-TAU * u as f64);
+        }
+        let per_register = (sum / m).powf(-1.0 / TAU);
+        if per_register < FGRA_MIN_ITEMS_PER_REGISTER {
+            return self.count_ml();
+        }
+        // Second order correction of the bias from raising the sum to `-1 / TAU`.
+        let bias = 1.0 + (1.0 + TAU) * FGRA_VARIANCE / (2.0 * m);
+        m * per_register / bias
+    }
+
+    /// Guess the number of unique elements with the maximum-likelihood estimator. The small
+    /// `O(1/m)` bias of the ML estimate is not corrected.
+    pub fn count_ml(&self) -> f64 {
+        // Under the Poisson model with rate x per register, each register contributes
+        // `-x * 2^-j` to the log-likelihood for update values known to be unseen, and
+        // `ln(1 - exp(-x * 2^-j))` for ones known to be seen, where 2^-j is the
+        // probability of the update value.
+        let max_k = 65 - self.precision as u32;
+        let mut unseen = 0.0f64;
+        let mut seen = [0u32; 64];
+        for &r in self.registers.iter() {
+            if r == 0 {
+                unseen += 1.0;
+                continue;
+            }
+            let u = (r >> 2) as u32;
+            if u < max_k {
+                unseen += 2.0f64.powi(-(u as i32));
+                seen[u as usize] += 1;
+            } else {
+                // The largest update value takes the remaining probability mass.
+                seen[(max_k - 1) as usize] += 1;
+            }
+            for (j, bit) in [(u - 1, 2), (u.wrapping_sub(2), 1)] {
+                if j == 0 || j > u {
+                    continue;
+                }
+                if r & bit != 0 {
+                    seen[j as usize] += 1;
+                } else {
+                    unseen += 2.0f64.powi(-(j as i32));
+                }
+            }
+        }
+        self.registers.len() as f64 * crate::estimators::solve_ml(unseen, &seen)
+    }
+}
+
+impl<S> AsRef<[u8]> for UltraLogLog<S> {
+    fn as_ref(&self) -> &[u8] {
+        &self.registers
+    }
+}
+
+/// Bit mask of the update values a register has seen (only the top three are kept).
+#[inline]
+fn unpack(register: u8) -> u64 {
+    let u = register >> 2;
+    if u == 0 {
+        0
+    } else {
+        ((4 | (register as u64 & 3)) << u) >> 2
+    }
+}
+
+#[inline]
+fn pack(seen: u64) -> u8 {
+    if seen == 0 {
+        return 0;
+    }
+    let u = 63 - seen.leading_zeros();
+    let history = ((seen << 2) >> u) & 3;
+    ((u << 2) as u8) | history as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ahash::RandomState;
+
+    fn new_ull(precision: u8) -> UltraLogLog<RandomState> {
+        UltraLogLog::with_hasher(precision, RandomState::with_seeds(1, 2, 3, 4))
+    }
+
+    /// Deterministic, well mixed hashes (splitmix64).
+    fn hashes(seed: u64) -> impl Iterator<Item = u64> {
+        let mut state = seed;
+        std::iter::repeat_with(move || {
+            state = state.wrapping_add(0x9E3779B97F4A7C15);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+            z ^ (z >> 31)
+        })
+    }
+
+    fn compare_with_delta(got: f64, expected: usize, precision: u8) {
+        let expected = expected as f64;
+        let diff = (got - expected).abs() / expected;
+        // 6 standard errors of HLL, which is less accurate, so the tests are stable
+        let margin = 1.04 / ((1u64 << precision) as f64).sqrt() * 6.0;
+        assert!(
+            diff <= margin,
+            "{} is not within {} of {}",
+            got,
+            margin,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_empty() {
+        let ull = new_ull(10);
+        assert_eq!(ull.count(), 0.0);
+        assert_eq!(ull.count_ml(), 0.0);
+    }
+
+    #[test]
+    fn test_one() {
+        let mut ull = new_ull(10);
+        ull.insert(&1u64);
+        assert!((ull.count() - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_unsupported_precision() {
+        new_ull(3);
+    }
+
+    #[test]
+    fn test_pack_unpack() {
+        for u in 1..=61u8 {
+            for history in 0..4u8 {
+                let register = (u << 2) | history;
+                if (u == 1 && history != 0) || (u == 2 && history & 1 != 0) {
+                    continue;
+                }
+                assert_eq!(pack(unpack(register)), register);
+            }
+        }
+        assert_eq!(pack(unpack(0)), 0);
+        // Only the top three update values are kept.
+        assert_eq!(pack(0b1111110), (6 << 2) | 3);
+        assert_eq!(pack(0b1001010), 6 << 2);
+    }
+
+    #[test]
+    fn test_merge_is_exact() {
+        let mut a = new_ull(8);
+        let mut b = new_ull(8);
+        let mut all = new_ull(8);
+        for hash in hashes(1).take(10_000) {
+            a.insert_hash(hash);
+            all.insert_hash(hash);
+        }
+        for hash in hashes(2).take(3_000) {
+            b.insert_hash(hash);
+            all.insert_hash(hash);
+        }
+        a.merge(&b);
+        assert_eq!(a.as_ref(), all.as_ref());
+    }
+
+    #[test]
+    fn test_merge_sparse() {
+        // Most registers are still empty in both sketches.
+        let mut a = new_ull(12);
+        let mut b = new_ull(12);
+        let mut all = new_ull(12);
+        for hash in hashes(3).take(10) {
+            a.insert_hash(hash);
+            all.insert_hash(hash);
+        }
+        b.merge(&new_ull(12));
+        assert!(b.as_ref().iter().all(|&r| r == 0));
+        b.merge(&a);
+        assert_eq!(b.as_ref(), all.as_ref());
+    }
+
+    #[test]
+    fn test_accuracy() {
+        for p in [8, 10, 12, 14] {
+            let mut ull = new_ull(p);
+            let mut n = 0;
+            for size in [100, 1_000, 10_000, 100_000, 1_000_000, 4_000_000] {
+                for hash in hashes(p as u64).skip(n).take(size - n) {
+                    ull.insert_hash(hash);
+                }
+                n = size;
+                compare_with_delta(ull.count(), n, p);
+                compare_with_delta(ull.count_ml(), n, p);
+            }
+        }
+    }
+
+    #[test]
+    fn test_estimators_agree() {
+        let mut ull = new_ull(12);
+        for hash in hashes(9).take(1_000_000) {
+            ull.insert_hash(hash);
+        }
+        let (fgra, ml) = (ull.count(), ull.count_ml());
+        assert!((fgra - ml).abs() / ml < 0.01, "{} vs {}", fgra, ml);
+    }
+}